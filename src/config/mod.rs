@@ -1,12 +1,74 @@
+use chrono::Duration;
+use sha2::{Digest, Sha256};
 use std::env;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub mongodb_uri: String,
     pub database_name: String,
-    pub jwt_secret: String,
-    pub port: u16,
-    pub address: String,
+    /// Signs HS256 tokens; also verifies them after a switch to RS256 or EdDSA
+    pub jwt_secret: Option<String>,
+    pub jwt_algorithm: String, // HS256, RS256 or EdDSA
+    /// Private key (PEM) for RS256 or EdDSA, and its `kid`
+    pub jwt_signing_key_file: Option<String>,
+    pub jwt_signing_key_id: Option<String>,
+    /// Public keys (PEM) accepted for verification, one `<kid>.pem` per key
+    pub jwt_verification_keys_dir: Option<String>,
+    /// `iss` and `aud` of issued tokens; tokens with other values are rejected
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
+    /// Clock skew tolerated when checking `exp` and `nbf`
+    pub jwt_leeway_seconds: u64,
+    pub access_token_ttl_minutes: i64,
+    pub refresh_token_ttl_days: i64,
+    pub reset_token_ttl_minutes: i64,
+    pub verification_token_ttl_hours: i64,
+    /// Minimum wait between two verification emails to the same account
+    pub verification_resend_seconds: i64,
+    /// Block todo routes until the account's email is verified
+    pub require_verified_email: bool,
+    pub password_algorithm: String, // argon2id or bcrypt
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub bcrypt_cost: u32,
+    /// Failed logins allowed per account before lockouts start
+    pub login_max_failures: u32,
+    /// Failed logins allowed per IP address before lockouts start
+    pub login_ip_max_failures: u32,
+    /// First lockout; doubles with every further failure
+    pub login_lockout_seconds: u64,
+    pub login_max_lockout_minutes: u64,
+    /// Name shown in authenticator apps
+    pub mfa_issuer: String,
+    /// Encrypts stored TOTP secrets; falls back to `jwt_secret` when unset.
+    /// Required for MFA when there is no `jwt_secret`
+    pub mfa_encryption_key: Option<String>,
+    /// Base URL of the web app, used for links in emails
+    pub app_url: String,
+    pub mail_transport: String, // smtp, file or memory
+    pub mail_from: String,
+    pub smtp_url: Option<String>,
+    pub mail_outbox_dir: String,
+    /// Sign-in providers, from `OIDC_PROVIDERS`
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    /// Allow `http://` provider URLs, for a local mock provider
+    pub oidc_allow_http: bool,
+}
+
+/// An OpenID Connect provider, configured through `OIDC_<NAME>_*` variables.
+#[derive(Debug, Clone)]
+pub struct OidcProviderConfig {
+    /// Lowercase; the `<provider>` in `/api/auth/oidc/<provider>`
+    pub name: String,
+    pub issuer: String,
+    /// Defaults to `<issuer>/.well-known/openid-configuration`
+    pub discovery_url: Option<String>,
+    pub client_id: String,
+    /// Unset for public clients, which rely on PKCE alone
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
 }
 
 impl Config {
@@ -18,14 +80,146 @@ impl Config {
                 .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
             database_name: env::var("DATABASE_NAME")
                 .unwrap_or_else(|_| "todo_rust_db".to_string()),
-            jwt_secret: env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "default_secret_change_me".to_string()),
-            port: env::var("ROCKET_PORT")
-                .unwrap_or_else(|_| "8000".to_string())
-                .parse()
-                .unwrap_or(8000),
-            address: env::var("ROCKET_ADDRESS")
-                .unwrap_or_else(|_| "127.0.0.1".to_string()),
+            jwt_secret: env::var("JWT_SECRET").ok(),
+            jwt_algorithm: env::var("JWT_ALGORITHM")
+                .unwrap_or_else(|_| "HS256".to_string()),
+            jwt_signing_key_file: env::var("JWT_SIGNING_KEY_FILE").ok(),
+            jwt_signing_key_id: env::var("JWT_SIGNING_KEY_ID").ok(),
+            jwt_verification_keys_dir: env::var("JWT_VERIFICATION_KEYS_DIR").ok(),
+            jwt_issuer: env::var("JWT_ISSUER")
+                .unwrap_or_else(|_| "todo_rust".to_string()),
+            jwt_audience: env::var("JWT_AUDIENCE")
+                .unwrap_or_else(|_| "todo_rust".to_string()),
+            jwt_leeway_seconds: env::var("JWT_LEEWAY_SECONDS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            access_token_ttl_minutes: env::var("ACCESS_TOKEN_TTL_MINUTES")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .unwrap_or(15),
+            refresh_token_ttl_days: env::var("REFRESH_TOKEN_TTL_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            reset_token_ttl_minutes: env::var("RESET_TOKEN_TTL_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            verification_token_ttl_hours: env::var("VERIFICATION_TOKEN_TTL_HOURS")
+                .unwrap_or_else(|_| "48".to_string())
+                .parse()
+                .unwrap_or(48),
+            verification_resend_seconds: env::var("VERIFICATION_RESEND_SECONDS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            require_verified_email: env::var("REQUIRE_VERIFIED_EMAIL")
+                .map(|value| value == "true")
+                .unwrap_or(false),
+            password_algorithm: env::var("PASSWORD_ALGORITHM")
+                .unwrap_or_else(|_| "argon2id".to_string()),
+            argon2_memory_kib: env::var("ARGON2_MEMORY_KIB")
+                .unwrap_or_else(|_| "19456".to_string())
+                .parse()
+                .unwrap_or(19456),
+            argon2_iterations: env::var("ARGON2_ITERATIONS")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()
+                .unwrap_or(2),
+            argon2_parallelism: env::var("ARGON2_PARALLELISM")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .unwrap_or(1),
+            bcrypt_cost: env::var("BCRYPT_COST")
+                .unwrap_or_else(|_| "12".to_string())
+                .parse()
+                .unwrap_or(12),
+            login_max_failures: env::var("LOGIN_MAX_FAILURES")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            login_ip_max_failures: env::var("LOGIN_IP_MAX_FAILURES")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .unwrap_or(20),
+            login_lockout_seconds: env::var("LOGIN_LOCKOUT_SECONDS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            login_max_lockout_minutes: env::var("LOGIN_MAX_LOCKOUT_MINUTES")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .unwrap_or(15),
+            mfa_issuer: env::var("MFA_ISSUER")
+                .unwrap_or_else(|_| "Todo".to_string()),
+            mfa_encryption_key: env::var("MFA_ENCRYPTION_KEY").ok(),
+            app_url: env::var("APP_URL")
+                .unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            mail_transport: env::var("MAIL_TRANSPORT")
+                .unwrap_or_else(|_| "file".to_string()),
+            mail_from: env::var("MAIL_FROM")
+                .unwrap_or_else(|_| "Todo <no-reply@localhost>".to_string()),
+            smtp_url: env::var("SMTP_URL").ok(),
+            mail_outbox_dir: env::var("MAIL_OUTBOX_DIR")
+                .unwrap_or_else(|_| "outbox".to_string()),
+            oidc_providers: oidc_providers()?,
+            oidc_allow_http: env::var("OIDC_ALLOW_HTTP")
+                .map(|value| value == "true")
+                .unwrap_or(false),
+        })
+    }
+}
+
+/// Reads `OIDC_PROVIDERS`, a comma-separated list of names, and for each
+/// name `OIDC_<NAME>_ISSUER`, `_CLIENT_ID` and `_REDIRECT_URI`, plus the
+/// optional `_DISCOVERY_URL`, `_CLIENT_SECRET` and `_SCOPES`.
+fn oidc_providers() -> Result<Vec<OidcProviderConfig>, env::VarError> {
+    let names = env::var("OIDC_PROVIDERS").unwrap_or_default();
+    names
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let var = |suffix: &str| {
+                env::var(format!("OIDC_{}_{}", name.to_uppercase(), suffix))
+            };
+            Ok(OidcProviderConfig {
+                issuer: var("ISSUER")?,
+                discovery_url: var("DISCOVERY_URL").ok(),
+                client_id: var("CLIENT_ID")?,
+                client_secret: var("CLIENT_SECRET").ok(),
+                redirect_uri: var("REDIRECT_URI")?,
+                scopes: var("SCOPES")
+                    .unwrap_or_else(|_| "openid email profile".to_string()),
+                name,
+            })
         })
+        .collect()
+}
+
+impl Config {
+    pub fn access_token_ttl(&self) -> Duration {
+        Duration::minutes(self.access_token_ttl_minutes)
+    }
+
+    pub fn refresh_token_ttl(&self) -> Duration {
+        Duration::days(self.refresh_token_ttl_days)
+    }
+
+    pub fn reset_token_ttl(&self) -> Duration {
+        Duration::minutes(self.reset_token_ttl_minutes)
+    }
+
+    pub fn verification_token_ttl(&self) -> Duration {
+        Duration::hours(self.verification_token_ttl_hours)
+    }
+
+    /// AES-256 key for TOTP secrets, derived from `MFA_ENCRYPTION_KEY`. Kept
+    /// apart from the JWT secret, so rotating that one does not lose the
+    /// secrets. `None` when it is not set.
+    pub fn mfa_key(&self) -> Option<[u8; 32]> {
+        let passphrase = self.mfa_encryption_key.as_ref()?;
+        Some(Sha256::digest(passphrase.as_bytes()).into())
     }
 }
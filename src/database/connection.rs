@@ -1,10 +1,20 @@
 use crate::config::Config;
 use anyhow::Result;
-use mongodb::{Client, Database};
+use mongodb::{
+    bson::doc,
+    error::{ErrorKind, WriteFailure},
+    options::IndexOptions,
+    Client, Database, IndexModel,
+};
+use std::time::Duration;
 
 pub struct DatabaseConnection {
     pub client: Client,
     pub database: Database,
+    /// Whether the deployment is a replica set or sharded cluster. A
+    /// standalone server accepts `start_transaction` but fails the first
+    /// write in it.
+    pub supports_transactions: bool,
 }
 
 impl DatabaseConnection {
@@ -18,10 +28,288 @@ impl DatabaseConnection {
             .await?;
         println!("Successfully connected to MongoDB!");
 
-        Ok(DatabaseConnection { client, database })
+        let hello = database.run_command(doc! {"hello": 1}).await?;
+        let supports_transactions =
+            hello.contains_key("setName") || hello.get_str("msg") == Ok("isdbgrid");
+
+        let connection = DatabaseConnection {
+            client,
+            database,
+            supports_transactions,
+        };
+        connection.ensure_indexes().await?;
+        connection.normalize_todo_dates().await?;
+
+        Ok(connection)
     }
 
     pub fn get_database(&self) -> &Database {
         &self.database
     }
+
+    /// Creates the indexes used by the todo queries. Safe to run on every start.
+    async fn ensure_indexes(&self) -> Result<()> {
+        let todos = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("todos");
+
+        todos
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "due_at": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "start_at": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "priority": -1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "tags": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "project_id": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "series_id": 1})
+                    .build(),
+                // Full-text search; the `user_id` prefix keeps each search within one user
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "title": "text", "description": "text"})
+                    .options(
+                        IndexOptions::builder()
+                            .name("todo_text".to_string())
+                            .weights(doc! {"title": 3, "description": 1})
+                            .build(),
+                    )
+                    .build(),
+                // Serves `overdue=true`: open todos of a user ordered by due date
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "completed": 1, "due_at": 1})
+                    .build(),
+            ])
+            .await?;
+
+        let tags = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("tags");
+        tags.create_index(
+            IndexModel::builder()
+                .keys(doc! {"user_id": 1, "name": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+        )
+        .await?;
+
+        let projects = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("projects");
+        projects
+            .create_index(IndexModel::builder().keys(doc! {"user_id": 1}).build())
+            .await?;
+
+        let views = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("views");
+        views
+            .create_index(IndexModel::builder().keys(doc! {"user_id": 1}).build())
+            .await?;
+
+        let refresh_tokens = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("refresh_tokens");
+        refresh_tokens
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"token_hash": 1})
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder().keys(doc! {"family_id": 1}).build(),
+                // Expired tokens are removed by MongoDB
+                IndexModel::builder()
+                    .keys(doc! {"expires_at": 1})
+                    .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+                    .build(),
+            ])
+            .await?;
+
+        let sessions = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("sessions");
+        sessions
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "last_seen_at": -1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"expires_at": 1})
+                    .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+                    .build(),
+            ])
+            .await?;
+
+        let one_time_tokens = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("one_time_tokens");
+        one_time_tokens
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"token_hash": 1})
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "purpose": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"expires_at": 1})
+                    .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+                    .build(),
+            ])
+            .await?;
+
+        let api_tokens = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("api_tokens");
+        api_tokens
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"token_hash": 1})
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"user_id": 1, "created_at": -1})
+                    .build(),
+                // Tokens without an expiry have no `expires_at` date and are kept
+                IndexModel::builder()
+                    .keys(doc! {"expires_at": 1})
+                    .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+                    .build(),
+            ])
+            .await?;
+
+        let login_attempts = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("login_attempts");
+        login_attempts
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"key": 1})
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"expires_at": 1})
+                    .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+                    .build(),
+            ])
+            .await?;
+
+        let oidc_logins = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("oidc_logins");
+        oidc_logins
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"state_hash": 1})
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"expires_at": 1})
+                    .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+                    .build(),
+            ])
+            .await?;
+
+        let users = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("users");
+        users
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"email": 1})
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                // One account per provider account; users without linked
+                // identities are left out, as they would all share a null key
+                IndexModel::builder()
+                    .keys(doc! {"identities.provider": 1, "identities.subject": 1})
+                    .options(
+                        IndexOptions::builder()
+                            .unique(true)
+                            .partial_filter_expression(
+                                doc! {"identities.subject": {"$exists": true}},
+                            )
+                            .build(),
+                    )
+                    .build(),
+            ])
+            .await?;
+
+        Ok(())
+    }
+
+    /// Rewrites `updated_at` values that older versions stored as BSON dates
+    /// into the RFC 3339 strings written today, so that sorting and cursors
+    /// compare a single type. Safe to run on every start.
+    async fn normalize_todo_dates(&self) -> Result<()> {
+        let todos = self
+            .get_database()
+            .collection::<mongodb::bson::Document>("todos");
+
+        // BSON dates have millisecond precision; padding to nanoseconds keeps
+        // the strings the same length as the ones chrono writes
+        todos
+            .update_many(
+                doc! {"updated_at": {"$type": "date"}},
+                vec![doc! {"$set": {"updated_at": {"$dateToString": {
+                    "date": "$updated_at",
+                    "format": "%Y-%m-%dT%H:%M:%S.%L000000Z",
+                }}}}],
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Whether a write failed because it would break a unique index.
+pub fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(
+        *error.kind,
+        ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == 11000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{oid::ObjectId, DateTime as BsonDateTime, Document};
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`).
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn rewrites_legacy_updated_at_dates() {
+        let config = Config {
+            mongodb_uri: std::env::var("MONGODB_TEST_URI")
+                .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
+            database_name: format!("todo_rust_test_{}", ObjectId::new().to_hex()),
+            ..Config::from_env().unwrap()
+        };
+        let db = DatabaseConnection::new(&config).await.unwrap();
+        let todos = db.database.collection::<Document>("todos");
+        let updated_at: chrono::DateTime<chrono::Utc> = "2026-01-15T09:30:00.123Z".parse().unwrap();
+        todos
+            .insert_one(doc! {"updated_at": BsonDateTime::from_chrono(updated_at)})
+            .await
+            .unwrap();
+
+        db.normalize_todo_dates().await.unwrap();
+
+        let todo = todos.find_one(doc! {}).await.unwrap().unwrap();
+        assert_eq!(
+            todo.get_str("updated_at"),
+            Ok("2026-01-15T09:30:00.123000000Z")
+        );
+        db.database.drop().await.unwrap();
+    }
 }
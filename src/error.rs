@@ -0,0 +1,311 @@
+use rocket::{
+    catch,
+    http::Status,
+    request::Request,
+    response::{self, Responder},
+    serde::json::Json,
+};
+use serde::Serialize;
+use validator::ValidationErrors;
+
+/// A problem with one field of the request.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// JSON body of every error response. `code` is stable and meant for
+/// programs; `error` is a human-readable message.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+}
+
+/// An error returned by a handler, rendered as JSON with the matching status.
+#[derive(Debug, Clone)]
+pub enum ApiError {
+    /// 400: malformed ids, query parameters or request bodies
+    BadRequest(ErrorResponse),
+    /// 401: missing or invalid credentials
+    Unauthorized(ErrorResponse),
+    /// 403: authenticated, but not allowed
+    Forbidden(ErrorResponse),
+    /// 404
+    NotFound(ErrorResponse),
+    /// 409: clashes with existing data or the resource's current state
+    Conflict(ErrorResponse),
+    /// 422: well-formed but invalid values
+    Unprocessable(ErrorResponse),
+    /// 429: slow down; `retry_after` seconds are sent as `Retry-After`
+    TooManyRequests {
+        body: ErrorResponse,
+        retry_after: u64,
+    },
+    /// 500: database outages and other failures on our side
+    Internal(ErrorResponse),
+    /// Any other status, for errors raised outside handlers
+    Other { status: Status, body: ErrorResponse },
+}
+
+fn body(code: &'static str, message: impl Into<String>) -> ErrorResponse {
+    ErrorResponse {
+        error: message.into(),
+        code,
+        details: Vec::new(),
+    }
+}
+
+impl ApiError {
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        ApiError::BadRequest(body(code, message))
+    }
+
+    pub fn unauthorized(code: &'static str, message: impl Into<String>) -> Self {
+        ApiError::Unauthorized(body(code, message))
+    }
+
+    pub fn forbidden(code: &'static str, message: impl Into<String>) -> Self {
+        ApiError::Forbidden(body(code, message))
+    }
+
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> Self {
+        ApiError::NotFound(body(code, message))
+    }
+
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
+        ApiError::Conflict(body(code, message))
+    }
+
+    pub fn unprocessable(code: &'static str, message: impl Into<String>) -> Self {
+        ApiError::Unprocessable(body(code, message))
+    }
+
+    pub fn too_many_requests(
+        code: &'static str,
+        message: impl Into<String>,
+        retry_after: u64,
+    ) -> Self {
+        ApiError::TooManyRequests {
+            body: body(code, message),
+            retry_after,
+        }
+    }
+
+    pub fn internal(code: &'static str, message: impl Into<String>) -> Self {
+        ApiError::Internal(body(code, message))
+    }
+
+    pub fn with_status(status: Status, code: &'static str, message: impl Into<String>) -> Self {
+        ApiError::Other {
+            status,
+            body: body(code, message),
+        }
+    }
+
+    /// A 422 for a single invalid field.
+    pub fn validation(field: &str, message: impl Into<String>) -> Self {
+        let message = message.into();
+        ApiError::unprocessable("validation_failed", message.clone())
+            .with_details(vec![FieldError::new(field, message)])
+    }
+
+    /// Shorthand for the most common 500.
+    pub fn database(message: impl Into<String>) -> Self {
+        ApiError::internal("database_error", message)
+    }
+
+    pub fn with_details(mut self, details: Vec<FieldError>) -> Self {
+        self.body_mut().details = details;
+        self
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::Unauthorized(_) => Status::Unauthorized,
+            ApiError::Forbidden(_) => Status::Forbidden,
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::Conflict(_) => Status::Conflict,
+            ApiError::Unprocessable(_) => Status::UnprocessableEntity,
+            ApiError::TooManyRequests { .. } => Status::TooManyRequests,
+            ApiError::Internal(_) => Status::InternalServerError,
+            ApiError::Other { status, .. } => *status,
+        }
+    }
+
+    pub fn body(&self) -> &ErrorResponse {
+        match self {
+            ApiError::BadRequest(body)
+            | ApiError::Unauthorized(body)
+            | ApiError::Forbidden(body)
+            | ApiError::NotFound(body)
+            | ApiError::Conflict(body)
+            | ApiError::Unprocessable(body)
+            | ApiError::TooManyRequests { body, .. }
+            | ApiError::Internal(body)
+            | ApiError::Other { body, .. } => body,
+        }
+    }
+
+    fn body_mut(&mut self) -> &mut ErrorResponse {
+        match self {
+            ApiError::BadRequest(body)
+            | ApiError::Unauthorized(body)
+            | ApiError::Forbidden(body)
+            | ApiError::NotFound(body)
+            | ApiError::Conflict(body)
+            | ApiError::Unprocessable(body)
+            | ApiError::TooManyRequests { body, .. }
+            | ApiError::Internal(body)
+            | ApiError::Other { body, .. } => body,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.body().error, self.body().code)
+    }
+}
+
+/// Every failing field at once, sorted by field name.
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut details: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| {
+                    let message = match &error.message {
+                        Some(message) => message.to_string(),
+                        None => format!("is invalid ({})", error.code),
+                    };
+                    FieldError::new(field.as_ref(), message)
+                })
+            })
+            .collect();
+        details.sort_by(|a, b| a.field.cmp(&b.field));
+
+        ApiError::unprocessable("validation_failed", "Request validation failed")
+            .with_details(details)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = (self.status(), Json(self.body().clone())).respond_to(request)?;
+        if let ApiError::TooManyRequests { retry_after, .. } = self {
+            response.set_raw_header("Retry-After", retry_after.to_string());
+        }
+        Ok(response)
+    }
+}
+
+/// Lets request guards tell the catcher why they rejected a request; Rocket
+/// only forwards the status to catchers.
+pub struct GuardError(pub Option<ApiError>);
+
+/// Keeps `error` for the catcher and returns it with its status, as guards
+/// report failures.
+pub fn guard_failure(request: &Request<'_>, error: ApiError) -> (Status, ApiError) {
+    request.local_cache(|| GuardError(Some(error.clone())));
+    (error.status(), error)
+}
+
+/// Renders errors raised outside handlers (failed guards, unparsable bodies,
+/// unknown routes) in the same JSON shape.
+#[catch(default)]
+pub fn default_catcher(status: Status, request: &Request) -> ApiError {
+    if let Some(error) = &request.local_cache(|| GuardError(None)).0 {
+        return error.clone();
+    }
+
+    match status.code {
+        400 => ApiError::bad_request("bad_request", "The request could not be parsed"),
+        401 => ApiError::unauthorized("unauthorized", "Authentication required"),
+        403 => ApiError::forbidden("forbidden", "Not allowed"),
+        404 => ApiError::not_found("not_found", "Resource not found"),
+        422 => ApiError::unprocessable("invalid_body", "The request body is invalid"),
+        429 => ApiError::too_many_requests("too_many_requests", "Too many requests", 1),
+        500 => ApiError::internal("internal_error", "Internal server error"),
+        code if code < 500 => ApiError::with_status(status, "client_error", status.reason_lossy()),
+        _ => ApiError::with_status(status, "server_error", status.reason_lossy()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::CreateUserRequest;
+    use validator::Validate;
+
+    #[test]
+    fn validation_errors_list_every_failing_field() {
+        let request: CreateUserRequest = serde_json::from_value(serde_json::json!({
+            "email": "not-an-email",
+            "password": "short",
+            "name": "  ",
+        }))
+        .unwrap();
+
+        let error = ApiError::from(request.validate().unwrap_err());
+        assert_eq!(error.status(), Status::UnprocessableEntity);
+        let fields: Vec<&str> = error
+            .body()
+            .details
+            .iter()
+            .map(|detail| detail.field.as_str())
+            .collect();
+        assert_eq!(fields, ["email", "name", "password", "password"]);
+    }
+
+    #[rocket::get("/fail/<code>")]
+    fn fail(code: u16) -> Result<(), Status> {
+        Err(Status::new(code))
+    }
+
+    #[test]
+    fn catcher_keeps_the_status() {
+        let rocket = rocket::build()
+            .mount("/", rocket::routes![fail])
+            .register("/", rocket::catchers![default_catcher]);
+        let client = rocket::local::blocking::Client::untracked(rocket).unwrap();
+
+        for (code, expected) in [
+            (404, "not_found"),
+            (405, "client_error"),
+            (413, "client_error"),
+            (503, "server_error"),
+        ] {
+            let response = client.get(format!("/fail/{}", code)).dispatch();
+            assert_eq!(response.status().code, code);
+            let body: serde_json::Value = response.into_json().unwrap();
+            assert_eq!(body["code"], expected);
+            assert!(body["error"].is_string());
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let result = serde_json::from_value::<CreateUserRequest>(serde_json::json!({
+            "email": "ada@example.com",
+            "password": "passw0rd",
+            "name": "Ada",
+            "admin": true,
+        }));
+        assert!(result.is_err());
+    }
+}
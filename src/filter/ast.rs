@@ -0,0 +1,117 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use mongodb::bson::oid::ObjectId;
+
+use crate::models::todo::Priority;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition(Condition),
+}
+
+impl Expr {
+    /// The operands of a chain of one operator: `a AND b AND c` (nested
+    /// `And`s, however grouped) gives `[a, b, c]`. Anything else is its own
+    /// only operand.
+    pub fn chain(&self) -> Vec<&Expr> {
+        let mut operands = Vec::new();
+        let mut pending = vec![self];
+        while let Some(expr) = pending.pop() {
+            match (self, expr) {
+                (Expr::And(..), Expr::And(left, right)) | (Expr::Or(..), Expr::Or(left, right)) => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+                _ => operands.push(expr),
+            }
+        }
+        operands
+    }
+
+    /// How deeply the compiled query nests `$and`, `$or` and `$nor`, with
+    /// chains of one operator counted once.
+    pub fn nesting(&self) -> usize {
+        match self {
+            Expr::And(..) | Expr::Or(..) => {
+                1 + self
+                    .chain()
+                    .into_iter()
+                    .map(Expr::nesting)
+                    .max()
+                    .unwrap_or(0)
+            }
+            Expr::Not(inner) => 1 + inner.nesting(),
+            Expr::Condition(_) => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `~`: case-insensitive substring match, text fields only
+    Contains,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateField {
+    Due,
+    Start,
+    Completed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Title,
+    Description,
+}
+
+/// Dates and weeks cover the whole period in the user's time zone; a timestamp
+/// is exact. Relative values are resolved when the filter is compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateValue {
+    Day(NaiveDate),
+    /// `today`, `tomorrow`, `today+3d`: days from today
+    RelativeDay(i64),
+    /// `this_week`, `next_week`, `last_week`: weeks from this one
+    RelativeWeek(i64),
+    Instant(DateTime<Utc>),
+}
+
+/// A single, already type-checked comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Completed {
+        op: Op,
+        value: bool,
+    },
+    Priority {
+        op: Op,
+        value: Priority,
+    },
+    /// `value` is `None` for `due = none`
+    Date {
+        field: DateField,
+        op: Op,
+        value: Option<DateValue>,
+    },
+    Text {
+        field: TextField,
+        op: Op,
+        value: String,
+    },
+    /// `tag:name`
+    Tag(String),
+    /// `project = <id>`, `None` for the Inbox
+    Project {
+        op: Op,
+        value: Option<ObjectId>,
+    },
+}
@@ -0,0 +1,199 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use mongodb::bson::{doc, Bson, DateTime as BsonDateTime, Document};
+
+use super::ast::{Condition, DateField, DateValue, Expr, Op, TextField};
+use crate::utils::time::{start_of_day, this_week_bounds};
+
+/// Compiles a parsed filter into a MongoDB query. Every field name and
+/// operator comes from the AST, so user input only ever ends up as a value.
+/// Relative dates such as `today` are resolved against `now` in `tz`.
+pub fn compile(expr: &Expr, tz: Tz, now: DateTime<Utc>) -> Result<Document, String> {
+    match expr {
+        // One `$and`/`$or` per chain; MongoDB limits how deeply queries nest
+        Expr::And(..) => Ok(doc! {"$and": compile_chain(expr, tz, now)?}),
+        Expr::Or(..) => Ok(doc! {"$or": compile_chain(expr, tz, now)?}),
+        Expr::Not(inner) => Ok(doc! {"$nor": [compile(inner, tz, now)?]}),
+        Expr::Condition(condition) => compile_condition(condition, tz, now),
+    }
+}
+
+fn compile_chain(expr: &Expr, tz: Tz, now: DateTime<Utc>) -> Result<Vec<Document>, String> {
+    expr.chain()
+        .into_iter()
+        .map(|operand| compile(operand, tz, now))
+        .collect()
+}
+
+fn compile_condition(
+    condition: &Condition,
+    tz: Tz,
+    now: DateTime<Utc>,
+) -> Result<Document, String> {
+    let compiled = match condition {
+        Condition::Completed { op, value } => compare("completed", *op, *value),
+        Condition::Priority { op, value } => compare("priority", *op, value.rank()),
+        Condition::Date { field, op, value } => {
+            let field = match field {
+                DateField::Due => "due_at",
+                DateField::Start => "start_at",
+                DateField::Completed => "completed_at",
+            };
+            let (start, end) = match value {
+                None => return Ok(compare(field, *op, Bson::Null)),
+                Some(DateValue::Instant(instant)) => {
+                    return Ok(compare(field, *op, bson_date(*instant)))
+                }
+                Some(DateValue::Day(date)) => day_bounds(*date, tz)?,
+                Some(DateValue::RelativeDay(days)) => {
+                    let today = now.with_timezone(&tz).date_naive();
+                    day_bounds(today + Duration::days(*days), tz)?
+                }
+                Some(DateValue::RelativeWeek(weeks)) => {
+                    this_week_bounds(now + Duration::weeks(*weeks), tz)
+                        .map_err(|e| e.to_string())?
+                }
+            };
+            compare_range(field, *op, start, end)
+        }
+        Condition::Text { field, op, value } => {
+            let field = match field {
+                TextField::Title => "title",
+                TextField::Description => "description",
+            };
+            match op {
+                Op::Contains => doc! {field: {"$regex": escape_regex(value), "$options": "i"}},
+                _ => compare(field, *op, value.as_str()),
+            }
+        }
+        Condition::Tag(name) => doc! {"tags": name},
+        Condition::Project { op, value } => compare("project_id", *op, *value),
+    };
+    Ok(compiled)
+}
+
+fn compare(field: &str, op: Op, value: impl Into<Bson>) -> Document {
+    let value = value.into();
+    match op {
+        Op::Eq => doc! {field: value},
+        Op::Ne => doc! {field: {"$ne": value}},
+        Op::Lt => doc! {field: {"$lt": value}},
+        Op::Le => doc! {field: {"$lte": value}},
+        Op::Gt => doc! {field: {"$gt": value}},
+        Op::Ge => doc! {field: {"$gte": value}},
+        // The parser only allows `~` on text fields
+        Op::Contains => doc! {field: value},
+    }
+}
+
+fn day_bounds(date: NaiveDate, tz: Tz) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let start = start_of_day(date, tz).map_err(|e| e.to_string())?;
+    let end = start_of_day(date + Duration::days(1), tz).map_err(|e| e.to_string())?;
+    Ok((start, end))
+}
+
+/// A date or week stands for the whole local period `[start, end)`.
+fn compare_range(field: &str, op: Op, start: DateTime<Utc>, end: DateTime<Utc>) -> Document {
+    let (start, end) = (bson_date(start), bson_date(end));
+    match op {
+        Op::Lt => doc! {field: {"$lt": start}},
+        Op::Le => doc! {field: {"$lt": end}},
+        Op::Gt => doc! {field: {"$gte": end}},
+        Op::Ge => doc! {field: {"$gte": start}},
+        Op::Ne => doc! {"$nor": [{field: {"$gte": start, "$lt": end}}]},
+        Op::Eq | Op::Contains => doc! {field: {"$gte": start, "$lt": end}},
+    }
+}
+
+fn bson_date(datetime: DateTime<Utc>) -> BsonDateTime {
+    BsonDateTime::from_chrono(datetime)
+}
+
+fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\.+*?()|[]{}^$-".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::parser::parse;
+
+    fn compile_str(input: &str) -> Document {
+        compile(&parse(input).unwrap(), Tz::UTC, Utc::now()).unwrap()
+    }
+
+    #[test]
+    fn compiles_boolean_structure() {
+        assert_eq!(
+            compile_str("NOT completed = true OR priority >= high"),
+            doc! {"$or": [
+                {"$nor": [{"completed": true}]},
+                {"priority": {"$gte": 3}},
+            ]}
+        );
+    }
+
+    #[test]
+    fn chains_compile_to_one_operator() {
+        assert_eq!(
+            compile_str("tag:a OR tag:b OR (tag:c OR tag:d AND tag:e AND tag:f)"),
+            doc! {"$or": [
+                {"tags": "a"},
+                {"tags": "b"},
+                {"tags": "c"},
+                {"$and": [{"tags": "d"}, {"tags": "e"}, {"tags": "f"}]},
+            ]}
+        );
+
+        let long = vec!["tag:a"; 110].join(" OR ");
+        let compiled = compile_str(&long);
+        assert_eq!(compiled.get_array("$or").unwrap().len(), 110);
+    }
+
+    #[test]
+    fn plain_dates_cover_the_local_day() {
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let compiled = compile(&parse("due <= 2026-11-01").unwrap(), tz, Utc::now()).unwrap();
+        let end = "2026-11-01T23:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(compiled, doc! {"due_at": {"$lt": bson_date(end)}});
+    }
+
+    #[test]
+    fn text_values_are_escaped() {
+        assert_eq!(
+            compile_str(r#"title ~ ".*$where""#),
+            doc! {"title": {"$regex": r"\.\*\$where", "$options": "i"}}
+        );
+        assert_eq!(
+            compile_str(r#"title = "{\"$ne\": null}""#),
+            doc! {"title": r#"{"$ne": null}"#}
+        );
+    }
+
+    #[test]
+    fn relative_dates_resolve_against_now() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let now = "2026-10-21T02:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let at = |value: &str| bson_date(value.parse::<DateTime<Utc>>().unwrap());
+
+        // 22:00 on Tuesday 20 October in New York
+        let compiled = compile(&parse("due = today+1d").unwrap(), tz, now).unwrap();
+        assert_eq!(
+            compiled,
+            doc! {"due_at": {"$gte": at("2026-10-21T04:00:00Z"), "$lt": at("2026-10-22T04:00:00Z")}}
+        );
+
+        let compiled = compile(&parse("completed_at >= this_week").unwrap(), tz, now).unwrap();
+        assert_eq!(
+            compiled,
+            doc! {"completed_at": {"$gte": at("2026-10-19T04:00:00Z")}}
+        );
+    }
+}
@@ -0,0 +1,624 @@
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use mongodb::bson::oid::ObjectId;
+
+use super::ast::{Condition, DateField, DateValue, Expr, Op, TextField};
+use crate::{
+    error::{ApiError, FieldError},
+    models::{project::INBOX_ID, todo::Priority},
+};
+
+const MAX_LENGTH: usize = 1000;
+const MAX_DEPTH: usize = 32;
+
+/// A parse error, pointing at the 1-based character column where it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl FilterError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        FilterError {
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid filter at column {}: {}",
+            self.column, self.message
+        )
+    }
+}
+
+impl From<FilterError> for ApiError {
+    fn from(error: FilterError) -> Self {
+        ApiError::bad_request("invalid_filter", error.to_string())
+            .with_details(vec![FieldError::new("filter", error.to_string())])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Colon,
+    Op(Op),
+    Word(String),
+    Str(String),
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+/// Parses a filter expression such as
+/// `completed = false AND (priority >= high OR tag:urgent) AND due < 2026-11-01`.
+///
+/// `AND` binds tighter than `OR`, `NOT` tighter than both; keywords are
+/// case-insensitive. Timestamps and values with spaces must be double-quoted.
+pub fn parse(input: &str) -> Result<Expr, FilterError> {
+    if input.chars().count() > MAX_LENGTH {
+        return Err(FilterError::new(
+            MAX_LENGTH + 1,
+            format!("filter is longer than {} characters", MAX_LENGTH),
+        ));
+    }
+
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        depth: 0,
+    };
+    let expr = parser.parse_or()?;
+
+    let token = parser.peek();
+    if token.kind != TokenKind::End {
+        return Err(FilterError::new(
+            token.column,
+            format!("unexpected {}", describe(&token.kind)),
+        ));
+    }
+    // Parentheses alone do not bound this: each level can hold both an OR
+    // and an AND
+    if expr.nesting() > MAX_DEPTH {
+        return Err(FilterError::new(1, too_deep()));
+    }
+    Ok(expr)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FilterError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let (kind, length) = match c {
+            '(' => (TokenKind::LParen, 1),
+            ')' => (TokenKind::RParen, 1),
+            ':' => (TokenKind::Colon, 1),
+            '~' => (TokenKind::Op(Op::Contains), 1),
+            '=' if chars.get(i + 1) == Some(&'=') => (TokenKind::Op(Op::Eq), 2),
+            '=' => (TokenKind::Op(Op::Eq), 1),
+            '!' if chars.get(i + 1) == Some(&'=') => (TokenKind::Op(Op::Ne), 2),
+            '<' if chars.get(i + 1) == Some(&'=') => (TokenKind::Op(Op::Le), 2),
+            '<' => (TokenKind::Op(Op::Lt), 1),
+            '>' if chars.get(i + 1) == Some(&'=') => (TokenKind::Op(Op::Ge), 2),
+            '>' => (TokenKind::Op(Op::Gt), 1),
+            '"' => {
+                let mut value = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None => return Err(FilterError::new(column, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => match chars.get(j + 1) {
+                            Some(escaped @ ('"' | '\\')) => {
+                                value.push(*escaped);
+                                j += 2;
+                            }
+                            _ => return Err(FilterError::new(j + 1, "invalid escape sequence")),
+                        },
+                        Some(other) => {
+                            value.push(*other);
+                            j += 1;
+                        }
+                    }
+                }
+                (TokenKind::Str(value), j + 1 - i)
+            }
+            c if is_word_char(c) => {
+                let length = chars[i..].iter().take_while(|c| is_word_char(**c)).count();
+                (
+                    TokenKind::Word(chars[i..i + length].iter().collect()),
+                    length,
+                )
+            }
+            other => {
+                return Err(FilterError::new(
+                    column,
+                    format!("unexpected character '{}'", other),
+                ))
+            }
+        };
+
+        tokens.push(Token { kind, column });
+        i += length;
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::End,
+        column: chars.len() + 1,
+    });
+    Ok(tokens)
+}
+
+fn too_deep() -> String {
+    format!("filter is nested more than {} levels deep", MAX_DEPTH)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.')
+}
+
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::LParen => "'('".to_string(),
+        TokenKind::RParen => "')'".to_string(),
+        TokenKind::Colon => "':'".to_string(),
+        TokenKind::Op(_) => "operator".to_string(),
+        TokenKind::Word(word) => format!("'{}'", word),
+        TokenKind::Str(value) => format!("\"{}\"", value),
+        TokenKind::End => "end of filter".to_string(),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.parse_and()?;
+        while self.at_keyword("OR") {
+            self.next();
+            let right = self.parse_and()?;
+            expr = Expr::Or(Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.parse_unary()?;
+        while self.at_keyword("AND") {
+            self.next();
+            let right = self.parse_unary()?;
+            expr = Expr::And(Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, FilterError> {
+        if self.at_keyword("NOT") {
+            let token = self.next();
+            self.enter(token.column)?;
+            let expr = self.parse_unary()?;
+            self.depth -= 1;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, FilterError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::LParen => {
+                self.enter(token.column)?;
+                let expr = self.parse_or()?;
+                self.depth -= 1;
+                let closing = self.next();
+                if closing.kind != TokenKind::RParen {
+                    return Err(FilterError::new(
+                        closing.column,
+                        format!("expected ')' but found {}", describe(&closing.kind)),
+                    ));
+                }
+                Ok(expr)
+            }
+            TokenKind::Word(field) => self.parse_condition(&field, token.column),
+            other => Err(FilterError::new(
+                token.column,
+                format!("expected a condition but found {}", describe(&other)),
+            )),
+        }
+    }
+
+    fn enter(&mut self, column: usize) -> Result<(), FilterError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(FilterError::new(column, too_deep()));
+        }
+        Ok(())
+    }
+
+    fn parse_condition(&mut self, field: &str, column: usize) -> Result<Expr, FilterError> {
+        let field = field.to_ascii_lowercase();
+
+        if self.peek().kind == TokenKind::Colon {
+            self.next();
+            if field != "tag" {
+                return Err(FilterError::new(
+                    column,
+                    format!("'{}:' is not supported, only 'tag:'", field),
+                ));
+            }
+            let (value, _) = self.parse_value()?;
+            return Ok(Expr::Condition(Condition::Tag(value)));
+        }
+
+        let op_token = self.next();
+        let op = match op_token.kind {
+            TokenKind::Op(op) => op,
+            other => {
+                return Err(FilterError::new(
+                    op_token.column,
+                    format!("expected an operator but found {}", describe(&other)),
+                ))
+            }
+        };
+        let (value, value_column) = self.parse_value()?;
+
+        let check_op = |allowed: &[Op]| {
+            if allowed.contains(&op) {
+                Ok(())
+            } else {
+                Err(FilterError::new(
+                    op_token.column,
+                    format!("operator not supported for '{}'", field),
+                ))
+            }
+        };
+        const EQUALITY: &[Op] = &[Op::Eq, Op::Ne];
+        const ORDERING: &[Op] = &[Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge];
+
+        let condition = match field.as_str() {
+            "completed" => {
+                check_op(EQUALITY)?;
+                let value = match value.to_ascii_lowercase().as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(FilterError::new(value_column, "expected true or false")),
+                };
+                Condition::Completed { op, value }
+            }
+            "priority" => {
+                check_op(ORDERING)?;
+                let value = parse_priority(&value).ok_or_else(|| {
+                    FilterError::new(
+                        value_column,
+                        "expected a priority (none, low, medium, high, urgent)",
+                    )
+                })?;
+                Condition::Priority { op, value }
+            }
+            "due" | "due_at" | "start" | "start_at" | "completed_at" => {
+                check_op(ORDERING)?;
+                let field = match field.as_str() {
+                    "due" | "due_at" => DateField::Due,
+                    "start" | "start_at" => DateField::Start,
+                    _ => DateField::Completed,
+                };
+                let value = if value.eq_ignore_ascii_case("none") {
+                    check_op(EQUALITY)?;
+                    None
+                } else {
+                    Some(parse_date(&value).ok_or_else(|| {
+                        FilterError::new(
+                            value_column,
+                            "expected YYYY-MM-DD, today, this_week, a quoted RFC 3339 timestamp or none",
+                        )
+                    })?)
+                };
+                Condition::Date { field, op, value }
+            }
+            "title" | "description" => {
+                check_op(&[Op::Eq, Op::Ne, Op::Contains])?;
+                let field = if field == "title" {
+                    TextField::Title
+                } else {
+                    TextField::Description
+                };
+                Condition::Text { field, op, value }
+            }
+            "project" => {
+                check_op(EQUALITY)?;
+                let value = if value.eq_ignore_ascii_case(INBOX_ID) {
+                    None
+                } else {
+                    Some(ObjectId::parse_str(&value).map_err(|_| {
+                        FilterError::new(value_column, "expected a project ID or inbox")
+                    })?)
+                };
+                Condition::Project { op, value }
+            }
+            _ => {
+                return Err(FilterError::new(
+                    column,
+                    format!("unknown field '{}'", field),
+                ))
+            }
+        };
+
+        Ok(Expr::Condition(condition))
+    }
+
+    fn parse_value(&mut self) -> Result<(String, usize), FilterError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::Word(value) | TokenKind::Str(value) => Ok((value, token.column)),
+            other => Err(FilterError::new(
+                token.column,
+                format!("expected a value but found {}", describe(&other)),
+            )),
+        }
+    }
+}
+
+fn parse_priority(value: &str) -> Option<Priority> {
+    match value.to_ascii_lowercase().as_str() {
+        "none" => Some(Priority::None),
+        "low" => Some(Priority::Low),
+        "medium" => Some(Priority::Medium),
+        "high" => Some(Priority::High),
+        "urgent" => Some(Priority::Urgent),
+        _ => None,
+    }
+}
+
+fn parse_date(value: &str) -> Option<DateValue> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(DateValue::Instant(datetime.with_timezone(&Utc)));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(DateValue::Day(date));
+    }
+
+    match value.to_ascii_lowercase().as_str() {
+        "yesterday" => Some(DateValue::RelativeDay(-1)),
+        "today" => Some(DateValue::RelativeDay(0)),
+        "tomorrow" => Some(DateValue::RelativeDay(1)),
+        "last_week" => Some(DateValue::RelativeWeek(-1)),
+        "this_week" => Some(DateValue::RelativeWeek(0)),
+        "next_week" => Some(DateValue::RelativeWeek(1)),
+        value => {
+            // today+3d, today-2w
+            let offset = value.strip_prefix("today")?;
+            let (sign, amount) = match offset.split_at_checked(1)? {
+                ("+", amount) => (1, amount),
+                ("-", amount) => (-1, amount),
+                _ => return None,
+            };
+            let (count, days_per_unit) = if let Some(count) = amount.strip_suffix('d') {
+                (count, 1)
+            } else {
+                (amount.strip_suffix('w')?, 7)
+            };
+            if count.is_empty() || count.len() > 4 || !count.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let count: i64 = count.parse().ok()?;
+            Some(DateValue::RelativeDay(sign * count * days_per_unit))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completed(value: bool) -> Expr {
+        Expr::Condition(Condition::Completed { op: Op::Eq, value })
+    }
+
+    fn tag(name: &str) -> Expr {
+        Expr::Condition(Condition::Tag(name.to_string()))
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let expr = parse("completed = true OR completed = false AND tag:work").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Or(
+                Box::new(completed(true)),
+                Box::new(Expr::And(Box::new(completed(false)), Box::new(tag("work")))),
+            )
+        );
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        let expr = parse("(completed = true OR completed = false) AND tag:work").unwrap();
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::Or(
+                    Box::new(completed(true)),
+                    Box::new(completed(false))
+                )),
+                Box::new(tag("work")),
+            )
+        );
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        let expr = parse("NOT tag:a and tag:b").unwrap();
+        assert_eq!(
+            expr,
+            Expr::And(Box::new(Expr::Not(Box::new(tag("a")))), Box::new(tag("b")))
+        );
+    }
+
+    #[test]
+    fn operators_are_left_associative() {
+        let expr = parse("tag:a OR tag:b OR tag:c").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Or(
+                Box::new(Expr::Or(Box::new(tag("a")), Box::new(tag("b")))),
+                Box::new(tag("c")),
+            )
+        );
+    }
+
+    #[test]
+    fn parses_the_documented_example() {
+        let expr =
+            parse("completed = false AND (priority >= high OR tag:urgent) AND due < 2026-11-01")
+                .unwrap();
+        let due = Expr::Condition(Condition::Date {
+            field: DateField::Due,
+            op: Op::Lt,
+            value: Some(DateValue::Day(
+                NaiveDate::from_ymd_opt(2026, 11, 1).unwrap(),
+            )),
+        });
+        let priority = Expr::Condition(Condition::Priority {
+            op: Op::Ge,
+            value: Priority::High,
+        });
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::And(
+                    Box::new(completed(false)),
+                    Box::new(Expr::Or(Box::new(priority), Box::new(tag("urgent")))),
+                )),
+                Box::new(due),
+            )
+        );
+    }
+
+    #[test]
+    fn quoted_values_keep_spaces_and_escapes() {
+        let expr = parse(r#"title ~ "say \"hi\" now""#).unwrap();
+        assert_eq!(
+            expr,
+            Expr::Condition(Condition::Text {
+                field: TextField::Title,
+                op: Op::Contains,
+                value: r#"say "hi" now"#.to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_error_columns() {
+        assert_eq!(parse("completed = maybe").unwrap_err().column, 13);
+        assert_eq!(parse("tag:a AND").unwrap_err().column, 10);
+        assert_eq!(parse("(tag:a").unwrap_err().column, 7);
+        assert_eq!(parse("colour = red").unwrap_err().column, 1);
+        assert_eq!(parse("tag:a tag:b").unwrap_err().column, 7);
+        assert_eq!(parse("title ~ \"open").unwrap_err().column, 9);
+        assert_eq!(parse("priority ~ high").unwrap_err().column, 10);
+    }
+
+    #[test]
+    fn rejects_mongo_operators_and_internal_fields() {
+        assert!(parse("$where = 1").is_err());
+        assert!(parse("user_id = 5f1d7f3e2b3a4c5d6e7f8a9b").is_err());
+        assert!(parse("_id = 5f1d7f3e2b3a4c5d6e7f8a9b").is_err());
+        assert!(parse(r#"title = {"$ne": null}"#).is_err());
+        assert!(parse("completed = false; db.todos.drop()").is_err());
+        assert!(parse("completed = false OR 1 = 1").is_err());
+        assert!(parse("tag:urgent' OR '1'='1").is_err());
+    }
+
+    #[test]
+    fn rejects_deep_nesting_and_long_input() {
+        let deep = format!("{}tag:a{}", "(".repeat(100), ")".repeat(100));
+        assert!(parse(&deep)
+            .unwrap_err()
+            .message
+            .contains("nested more than"));
+
+        let mixed = format!(
+            "{}tag:a{}",
+            "(tag:a OR tag:b AND ".repeat(20),
+            ")".repeat(20)
+        );
+        assert!(parse(&mixed)
+            .unwrap_err()
+            .message
+            .contains("nested more than"));
+
+        let long = vec!["tag:a"; 300].join(" OR ");
+        assert!(parse(&long).unwrap_err().message.contains("longer than"));
+    }
+
+    #[test]
+    fn long_chains_nest_once() {
+        let chain = vec!["tag:a"; 110].join(" OR ");
+        let expr = parse(&chain).unwrap();
+        assert_eq!(expr.chain().len(), 110);
+        assert_eq!(expr.nesting(), 1);
+
+        let expr = parse("NOT (tag:a OR tag:b AND tag:c) AND tag:d").unwrap();
+        assert_eq!(expr.nesting(), 4);
+    }
+
+    #[test]
+    fn parses_relative_dates() {
+        let date = |input: &str| match parse(input).unwrap() {
+            Expr::Condition(Condition::Date { value, .. }) => value,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(date("due < today"), Some(DateValue::RelativeDay(0)));
+        assert_eq!(date("due <= today+2w"), Some(DateValue::RelativeDay(14)));
+        assert_eq!(date("start > today-3d"), Some(DateValue::RelativeDay(-3)));
+        assert_eq!(
+            date("completed_at = THIS_WEEK"),
+            Some(DateValue::RelativeWeek(0))
+        );
+        assert!(parse("due < today+3").is_err());
+        assert!(parse("due < today++3d").is_err());
+    }
+}
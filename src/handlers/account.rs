@@ -0,0 +1,391 @@
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, Document},
+    Collection,
+};
+use rocket::{delete, get, patch, post, serde::json::Json, State};
+
+use crate::{
+    config::Config,
+    database::connection::{is_duplicate_key, DatabaseConnection},
+    error::ApiError,
+    handlers::{
+        auth::email_taken,
+        one_time_token::{consume_one_time_token, issue_one_time_token},
+        session::revoke_sessions,
+    },
+    mail::{send_later, Email, SharedMailer},
+    middleware::{
+        auth::AuthenticatedUser,
+        scope::{ManageAccount, Scoped},
+        validated_json::ValidatedJson,
+    },
+    models::api_token::ApiToken,
+    models::one_time_token::{ConfirmEmailChangeRequest, TokenPurpose},
+    models::user::{
+        ChangeEmailRequest, ChangePasswordRequest, DeleteAccountRequest, UpdateProfileRequest,
+        User, UserResponse,
+    },
+    utils::password::Passwords,
+};
+
+/// Collections whose documents belong to a user through `user_id`, removed
+/// with the account.
+const USER_COLLECTIONS: [&str; 8] = [
+    "todos",
+    "tags",
+    "projects",
+    "views",
+    "sessions",
+    "refresh_tokens",
+    "one_time_tokens",
+    "api_tokens",
+];
+
+#[get("/me")]
+pub async fn get_me(
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<UserResponse>, ApiError> {
+    let account = load_account(&user, db).await?;
+    Ok(Json(UserResponse::from(account)))
+}
+
+#[patch("/me", data = "<request>")]
+pub async fn update_me(
+    request: ValidatedJson<UpdateProfileRequest>,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<UserResponse>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+    let user_id = user.object_id()?;
+
+    let mut update_doc = doc! {"updated_at": to_bson(&Utc::now()).unwrap()};
+    if let Some(name) = &request.name {
+        update_doc.insert("name", name.trim());
+    }
+    if let Some(timezone) = &request.timezone {
+        update_doc.insert("timezone", timezone);
+    }
+
+    collection
+        .update_one(doc! {"_id": user_id}, doc! {"$set": update_doc})
+        .await
+        .map_err(|_| ApiError::database("Failed to update profile"))?;
+
+    let account = load_account(&user, db).await?;
+    Ok(Json(UserResponse::from(account)))
+}
+
+/// Changes the password and logs out every other session; the session making
+/// the request stays signed in.
+#[post("/me/password", data = "<request>")]
+pub async fn change_password(
+    request: ValidatedJson<ChangePasswordRequest>,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+    passwords: &State<Passwords>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+    let user_id = user.object_id()?;
+
+    check_password(
+        &user,
+        &request.current_password,
+        "current_password",
+        db,
+        passwords,
+    )
+    .await?;
+
+    let password_hash = passwords
+        .hash(&request.new_password)
+        .map_err(|_| ApiError::internal("internal_error", "Failed to hash password"))?;
+    collection
+        .update_one(
+            doc! {"_id": user_id},
+            doc! {"$set": {
+                "password_hash": password_hash,
+                "updated_at": to_bson(&Utc::now()).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to update password"))?;
+
+    let mut others = doc! {"user_id": user_id};
+    if let Some(current) = user.session_object_id()? {
+        others.insert("_id", doc! {"$ne": current});
+    }
+    revoke_sessions(others, db).await?;
+    // Whoever knew the old password may have created tokens with it
+    db.database
+        .collection::<ApiToken>("api_tokens")
+        .delete_many(doc! {"user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to revoke tokens"))?;
+
+    Ok(Json(serde_json::json!({"message": "Password changed"})))
+}
+
+/// Starts an email change. The address only changes once the link sent to
+/// the new address is confirmed; the old address gets a notice.
+#[post("/me/email-change", data = "<request>")]
+pub async fn request_email_change(
+    request: ValidatedJson<ChangeEmailRequest>,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    mailer: &State<SharedMailer>,
+    passwords: &State<Passwords>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let account = check_password(&user, &request.password, "password", db, passwords).await?;
+
+    if request.new_email == account.email {
+        return Err(ApiError::validation(
+            "new_email",
+            "New email is the same as the current one",
+        ));
+    }
+
+    let token = issue_one_time_token(
+        account.id.unwrap(),
+        TokenPurpose::EmailChange,
+        Some(&request.new_email),
+        config.verification_token_ttl(),
+        db,
+    )
+    .await?;
+
+    send_later(
+        mailer.inner(),
+        Email {
+            to: request.new_email.clone(),
+            subject: "Confirm your new email address".to_string(),
+            body: format!(
+                "Open this link within {} hours to make this your account's email address:\n\
+                 {}/confirm-email-change?token={}",
+                config.verification_token_ttl_hours, config.app_url, token
+            ),
+        },
+    );
+    send_later(
+        mailer.inner(),
+        Email {
+            to: account.email,
+            subject: "Email change requested".to_string(),
+            body: format!(
+                "Someone asked to change your account's email address to {}.\n\
+                 If this wasn't you, change your password.",
+                request.new_email
+            ),
+        },
+    );
+
+    Ok(Json(serde_json::json!({
+        "message": "Confirmation email sent to the new address"
+    })))
+}
+
+/// Completes an email change with the token from the confirmation email.
+#[post("/me/email-change/confirm", data = "<request>")]
+pub async fn confirm_email_change(
+    request: ValidatedJson<ConfirmEmailChangeRequest>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<UserResponse>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let token = consume_one_time_token(&request.token, TokenPurpose::EmailChange, db)
+        .await?
+        .ok_or_else(|| {
+            ApiError::bad_request(
+                "invalid_email_change_token",
+                "Invalid, expired or already used confirmation token",
+            )
+        })?;
+    let email = token.email.ok_or_else(|| {
+        ApiError::internal("invalid_data", "Email change token without an address")
+    })?;
+
+    collection
+        .update_one(
+            doc! {"_id": token.user_id},
+            doc! {"$set": {
+                "email": &email,
+                "email_verified": true,
+                "updated_at": to_bson(&Utc::now()).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|error| {
+            // Taken since the change was requested
+            if is_duplicate_key(&error) {
+                email_taken()
+            } else {
+                ApiError::database("Failed to change email")
+            }
+        })?;
+
+    collection
+        .find_one(doc! {"_id": token.user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .map(|account| Json(UserResponse::from(account)))
+        .ok_or_else(|| ApiError::not_found("user_not_found", "User not found"))
+}
+
+/// Deletes the account and everything that belongs to it. Requires the
+/// password.
+#[delete("/me", data = "<request>")]
+pub async fn delete_me(
+    request: ValidatedJson<DeleteAccountRequest>,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+    passwords: &State<Passwords>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_password(&user, &request.password, "password", db, passwords).await?;
+
+    delete_account(user.object_id()?, db)
+        .await
+        .map_err(|_| ApiError::database("Failed to delete account"))?;
+
+    Ok(Json(serde_json::json!({"message": "Account deleted"})))
+}
+
+pub async fn load_account(
+    user: &AuthenticatedUser,
+    db: &DatabaseConnection,
+) -> Result<User, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    collection
+        .find_one(doc! {"_id": user.object_id()?})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .ok_or_else(|| ApiError::unauthorized("invalid_token", "User no longer exists"))
+}
+
+/// Re-authenticates a sensitive request. A wrong password is reported on
+/// `field`.
+pub async fn check_password(
+    user: &AuthenticatedUser,
+    password: &str,
+    field: &str,
+    db: &DatabaseConnection,
+    passwords: &Passwords,
+) -> Result<User, ApiError> {
+    let account = load_account(user, db).await?;
+
+    let valid = passwords
+        .verify(password, &account.password_hash)
+        .map_err(|_| ApiError::internal("internal_error", "Password verification failed"))?;
+    if !valid {
+        return Err(ApiError::validation(field, "Password is incorrect"));
+    }
+    Ok(account)
+}
+
+/// Removes the user and their data in one transaction. Deployments without
+/// transaction support (a standalone server) delete the same data without one.
+async fn delete_account(user_id: ObjectId, db: &DatabaseConnection) -> mongodb::error::Result<()> {
+    let users: Collection<Document> = db.database.collection("users");
+
+    if !db.supports_transactions {
+        for name in USER_COLLECTIONS {
+            let collection: Collection<Document> = db.database.collection(name);
+            collection.delete_many(doc! {"user_id": user_id}).await?;
+        }
+        users.delete_one(doc! {"_id": user_id}).await?;
+        return Ok(());
+    }
+
+    let mut session = db.client.start_session().await?;
+    session.start_transaction().await?;
+    for name in USER_COLLECTIONS {
+        let collection: Collection<Document> = db.database.collection(name);
+        collection
+            .delete_many(doc! {"user_id": user_id})
+            .session(&mut session)
+            .await?;
+    }
+    users
+        .delete_one(doc! {"_id": user_id})
+        .session(&mut session)
+        .await?;
+    session.commit_transaction().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::auth::create_user;
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`), standalone or a replica set.
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn deletes_the_account_and_its_data() {
+        let config = Config {
+            mongodb_uri: std::env::var("MONGODB_TEST_URI")
+                .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
+            database_name: format!("todo_rust_test_{}", ObjectId::new().to_hex()),
+            ..Config::from_env().unwrap()
+        };
+        let db = DatabaseConnection::new(&config).await.unwrap();
+        let now = Utc::now();
+        let user = |email: &str| User {
+            id: None,
+            email: email.to_string(),
+            password_hash: String::new(),
+            name: "Ada".to_string(),
+            timezone: None,
+            email_verified: true,
+            mfa: None,
+            is_admin: false,
+            identities: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        };
+        let user_id = create_user(user("ada@example.com"), &db)
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        let other_id = create_user(user("bob@example.com"), &db)
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        let tags: Collection<Document> = db.database.collection("tags");
+        tags.insert_many([
+            doc! {"user_id": user_id, "name": "home"},
+            doc! {"user_id": other_id, "name": "home"},
+        ])
+        .await
+        .unwrap();
+
+        delete_account(user_id, &db).await.unwrap();
+
+        let users: Collection<Document> = db.database.collection("users");
+        assert_eq!(
+            users.count_documents(doc! {"_id": user_id}).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            users.count_documents(doc! {"_id": other_id}).await.unwrap(),
+            1
+        );
+        for name in USER_COLLECTIONS {
+            let collection: Collection<Document> = db.database.collection(name);
+            let left = collection.count_documents(doc! {"user_id": user_id}).await;
+            assert_eq!(left.unwrap(), 0, "{} left behind", name);
+        }
+        assert_eq!(
+            tags.count_documents(doc! {"user_id": other_id})
+                .await
+                .unwrap(),
+            1
+        );
+
+        db.database.drop().await.unwrap();
+    }
+}
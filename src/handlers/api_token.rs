@@ -0,0 +1,127 @@
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    Collection,
+};
+use rocket::{delete, get, post, serde::json::Json, State};
+
+use crate::{
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    middleware::{
+        scope::{ManageAccount, Scoped},
+        validated_json::ValidatedJson,
+    },
+    models::api_token::{
+        ApiToken, ApiTokenResponse, CreateApiTokenRequest, CreatedApiToken, API_TOKEN_PREFIX,
+    },
+    utils::token::{generate_token, hash_token},
+};
+
+/// Characters of the token kept in `hint`, prefix included.
+const HINT_LENGTH: usize = 12;
+
+/// Lists the personal access tokens, newest first. Tokens themselves are
+/// never shown again after creation.
+#[get("/me/tokens")]
+pub async fn get_tokens(
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<Vec<ApiTokenResponse>>, ApiError> {
+    let collection: Collection<ApiToken> = db.database.collection("api_tokens");
+    let user_id = user.object_id()?;
+
+    let mut cursor = collection
+        .find(doc! {"user_id": user_id})
+        .sort(doc! {"created_at": -1})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch tokens"))?;
+
+    let mut tokens = Vec::new();
+    while cursor.advance().await.unwrap_or(false) {
+        if let Ok(token) = cursor.deserialize_current() {
+            tokens.push(ApiTokenResponse::from(token));
+        }
+    }
+    Ok(Json(tokens))
+}
+
+/// Creates a personal access token, used as `Authorization: Bearer pat_...`.
+/// A token can only grant scopes the caller's own token has, and only a
+/// login can create one, so a leaked token cannot mint longer-lived ones.
+#[post("/me/tokens", data = "<request>")]
+pub async fn create_token(
+    request: ValidatedJson<CreateApiTokenRequest>,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<CreatedApiToken>, ApiError> {
+    let collection: Collection<ApiToken> = db.database.collection("api_tokens");
+    let user_id = user.object_id()?;
+    if user.session_id.is_none() {
+        return Err(ApiError::forbidden(
+            "session_required",
+            "Personal access tokens can only be created after logging in",
+        ));
+    }
+
+    let mut scopes = Vec::new();
+    for scope in request.scopes.iter().copied() {
+        if !user.has_scope(scope) {
+            return Err(ApiError::forbidden(
+                "insufficient_scope",
+                format!("Cannot grant the {} scope", scope.as_str()),
+            ));
+        }
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+    let now = Utc::now();
+    let mut api_token = ApiToken {
+        id: None,
+        user_id,
+        name: request.name.trim().to_string(),
+        token_hash: hash_token(&token),
+        hint: token[..HINT_LENGTH].to_string(),
+        scopes,
+        expires_at: request
+            .expires_in_days
+            .map(|days| now + Duration::days(days)),
+        last_used_at: None,
+        created_at: now,
+    };
+
+    let result = collection
+        .insert_one(&api_token)
+        .await
+        .map_err(|_| ApiError::database("Failed to create token"))?;
+    api_token.id = result.inserted_id.as_object_id();
+
+    Ok(Json(CreatedApiToken {
+        details: ApiTokenResponse::from(api_token),
+        token,
+    }))
+}
+
+#[delete("/me/tokens/<id>")]
+pub async fn revoke_token(
+    id: String,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<ApiToken> = db.database.collection("api_tokens");
+    let token_id = ObjectId::parse_str(&id)
+        .map_err(|_| ApiError::bad_request("invalid_id", "Invalid token ID"))?;
+    let user_id = user.object_id()?;
+
+    let result = collection
+        .delete_one(doc! {"_id": token_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to revoke token"))?;
+    if result.deleted_count == 0 {
+        return Err(ApiError::not_found("token_not_found", "Token not found"));
+    }
+    Ok(Json(serde_json::json!({"message": "Token revoked"})))
+}
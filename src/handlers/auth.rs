@@ -1,162 +1,514 @@
-use anyhow::Result;
-use chrono::Utc;
-use mongodb::{bson::doc, Collection};
+use chrono::{DateTime, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime},
+    Collection,
+};
 use rocket::{post, serde::json::Json, State};
 
 use crate::{
     config::Config,
-    database::connection::DatabaseConnection,
+    database::connection::{is_duplicate_key, DatabaseConnection},
+    error::ApiError,
+    handlers::{
+        lockout::{check_lockout, clear_failed_logins, record_failed_login},
+        mfa::mfa_challenge,
+        session::{extend_session, revoke_sessions, start_session},
+        verification::send_verification_email,
+    },
+    mail::SharedMailer,
+    middleware::{client::ClientInfo, validated_json::ValidatedJson},
+    models::mfa::LoginResponse,
+    models::refresh_token::{RefreshRequest, RefreshToken, TokenResponse},
+    models::scope::all_scopes,
     models::user::{AuthResponse, CreateUserRequest, LoginRequest, User, UserResponse},
+    models::view::View,
     utils::{
-        jwt::create_jwt,
-        password::{hash_password, verify_password},
+        jwt::{create_jwt, JwtKeys},
+        password::Passwords,
+        token::{generate_token, hash_token},
     },
 };
 
-#[derive(rocket::serde::Serialize)]
-pub struct ErrorResponse {
-    pub error: String,
-}
-
 #[post("/signup", data = "<request>")]
 pub async fn signup(
-    request: Json<CreateUserRequest>,
+    request: ValidatedJson<CreateUserRequest>,
+    client: ClientInfo,
     db: &State<DatabaseConnection>,
     config: &State<Config>,
-) -> Result<Json<AuthResponse>, rocket::response::status::BadRequest<Json<ErrorResponse>>> {
+    keys: &State<JwtKeys>,
+    mailer: &State<SharedMailer>,
+    passwords: &State<Passwords>,
+) -> Result<Json<AuthResponse>, ApiError> {
+    // Hash password
+    let password_hash = passwords
+        .hash(&request.password)
+        .map_err(|_| ApiError::internal("internal_error", "Failed to hash password"))?;
+
+    let now = Utc::now();
+    let user = create_user(
+        User {
+            id: None,
+            email: request.email.clone(),
+            password_hash,
+            name: request.name.trim().to_string(),
+            timezone: request.timezone.clone(),
+            email_verified: false,
+            mfa: None,
+            is_admin: false,
+            identities: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        },
+        db,
+    )
+    .await?;
+    let object_id = user.id.unwrap();
+
+    send_verification_email(object_id, &user.email, db, config, mailer).await?;
+
+    let session_id = start_session(object_id, &client, db, config).await?;
+    let tokens = issue_tokens(object_id, &user.email, session_id, db, config, keys).await?;
+
+    Ok(Json(AuthResponse {
+        tokens,
+        user: UserResponse::from(user),
+    }))
+}
+
+/// Checks the password. Accounts with MFA enabled get a challenge to finish
+/// at `POST /auth/mfa` instead of tokens. Repeated failures lock the account
+/// and the client's IP address out for a while.
+#[post("/login", data = "<request>")]
+pub async fn login(
+    request: ValidatedJson<LoginRequest>,
+    client: ClientInfo,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    keys: &State<JwtKeys>,
+    passwords: &State<Passwords>,
+) -> Result<Json<LoginResponse>, ApiError> {
     let collection: Collection<User> = db.database.collection("users");
 
-    // Check if user already exists
-    let existing_user = collection.find_one(doc! {"email": &request.email}).await;
+    let ip = client.ip.as_deref();
+    check_lockout(&request.email, ip, db).await?;
+
+    // Find user by email
+    let user = collection
+        .find_one(doc! {"email": &request.email})
+        .await
+        .map_err(|_| ApiError::database("Database error"))?;
 
-    match existing_user {
-        Ok(Some(_)) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "User with this email already exists".to_string(),
-            })));
+    // Verify password; unknown emails cost a verification too, so response
+    // times do not reveal which emails have accounts
+    let valid = match &user {
+        Some(user) => passwords
+            .verify(&request.password, &user.password_hash)
+            .map_err(|_| ApiError::internal("internal_error", "Password verification failed"))?,
+        None => {
+            passwords.dummy_verify(&request.password);
+            false
         }
-        Ok(None) => {
-            // User doesn't exist, proceed with creation
+    };
+    let user = match user {
+        Some(user) if valid => user,
+        _ => {
+            record_failed_login(&request.email, ip, db, config).await?;
+            return Err(ApiError::unauthorized(
+                "invalid_credentials",
+                "Invalid credentials",
+            ));
         }
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Database error".to_string(),
-            })));
+    };
+
+    // Move hashes from an old algorithm or cost to the current settings,
+    // unless the password changed meanwhile
+    if passwords.needs_rehash(&user.password_hash) {
+        if let Ok(password_hash) = passwords.hash(&request.password) {
+            let _ = collection
+                .update_one(
+                    doc! {"_id": user.id, "password_hash": &user.password_hash},
+                    doc! {"$set": {"password_hash": password_hash}},
+                )
+                .await;
         }
     }
 
-    // Hash password
-    let password_hash = match hash_password(&request.password) {
-        Ok(hash) => hash,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Failed to hash password".to_string(),
-            })));
-        }
-    };
+    let object_id = user.id.unwrap();
+
+    // The failures are only forgotten once the second factor is verified too,
+    // so new challenges do not reset the count for code guessers
+    if user.mfa_enabled() {
+        let challenge = mfa_challenge(object_id, db).await?;
+        return Ok(Json(LoginResponse::MfaRequired(challenge)));
+    }
+    clear_failed_logins(&request.email, db).await?;
+
+    let session_id = start_session(object_id, &client, db, config).await?;
+    let tokens = issue_tokens(object_id, &user.email, session_id, db, config, keys).await?;
+
+    Ok(Json(LoginResponse::Authenticated(AuthResponse {
+        tokens,
+        user: UserResponse::from(user),
+    })))
+}
+
+/// Exchanges a refresh token for a new access token and a new refresh token.
+/// Each refresh token works once; presenting a rotated one again means it was
+/// copied, so the whole session is revoked and the client must log in again.
+#[post("/refresh", data = "<request>")]
+pub async fn refresh(
+    request: ValidatedJson<RefreshRequest>,
+    client: ClientInfo,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    keys: &State<JwtKeys>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let collection: Collection<RefreshToken> = db.database.collection("refresh_tokens");
+    let users: Collection<User> = db.database.collection("users");
+    let invalid =
+        || ApiError::unauthorized("invalid_refresh_token", "Invalid or expired refresh token");
 
     let now = Utc::now();
-    let user = User {
-        id: None,
-        email: request.email.clone(),
-        password_hash,
-        name: request.name.clone(),
-        created_at: now,
-        updated_at: now,
-    };
+    let token = collection
+        .find_one(doc! {"token_hash": hash_token(&request.refresh_token)})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch refresh token"))?
+        .ok_or_else(invalid)?;
+    match check_refresh_token(&token, now) {
+        Ok(()) => {}
+        Err(RefreshRejection::Invalid) => return Err(invalid()),
+        Err(RefreshRejection::Reused) => return Err(revoke_reused(&token, db).await),
+    }
 
-    // Insert user
-    let insert_result = collection.insert_one(&user).await;
-
-    match insert_result {
-        Ok(result) => {
-            let user_id = result.inserted_id.as_object_id().unwrap().to_hex();
-
-            // Create JWT token
-            let token = match create_jwt(user_id.clone(), request.email.clone(), &config.jwt_secret)
-            {
-                Ok(token) => token,
-                Err(_) => {
-                    return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                        error: "Failed to create token".to_string(),
-                    })));
-                }
-            };
-
-            let user_response = UserResponse {
-                id: user_id,
-                email: request.email.clone(),
-                name: request.name.clone(),
-                created_at: now,
-            };
+    // Claim the token atomically so two concurrent refreshes cannot both succeed
+    let claimed = collection
+        .find_one_and_update(
+            doc! {"_id": token.id, "rotated_at": null, "revoked_at": null},
+            doc! {"$set": {"rotated_at": BsonDateTime::from_chrono(now)}},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to rotate refresh token"))?;
+    if claimed.is_none() {
+        return Err(revoke_reused(&token, db).await);
+    }
 
-            Ok(Json(AuthResponse {
-                token,
-                user: user_response,
-            }))
-        }
-        Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Failed to create user".to_string(),
-        }))),
+    extend_session(token.family_id, &client, db, config).await?;
+
+    let user = users
+        .find_one(doc! {"_id": token.user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .ok_or_else(invalid)?;
+
+    let tokens = issue_tokens(
+        token.user_id,
+        &user.email,
+        token.family_id,
+        db,
+        config,
+        keys,
+    )
+    .await?;
+    Ok(Json(tokens))
+}
+
+/// Why a refresh token cannot be exchanged.
+#[derive(Debug, PartialEq, Eq)]
+enum RefreshRejection {
+    /// Revoked or expired
+    Invalid,
+    /// Already exchanged once, so someone holds a copy
+    Reused,
+}
+
+/// Decides whether `token` may be exchanged at `now`. Tokens of a revoked
+/// session are only invalid, so that replaying them does not revoke anything
+/// twice.
+fn check_refresh_token(token: &RefreshToken, now: DateTime<Utc>) -> Result<(), RefreshRejection> {
+    if token.revoked_at.is_some() || token.expires_at <= now {
+        return Err(RefreshRejection::Invalid);
     }
+    if token.rotated_at.is_some() {
+        return Err(RefreshRejection::Reused);
+    }
+    Ok(())
 }
 
-#[post("/login", data = "<request>")]
-pub async fn login(
-    request: Json<LoginRequest>,
+/// Revokes the session of a reused refresh token and returns the error for
+/// the client.
+async fn revoke_reused(token: &RefreshToken, db: &DatabaseConnection) -> ApiError {
+    match revoke_sessions(doc! {"_id": token.family_id}, db).await {
+        Ok(_) => ApiError::unauthorized(
+            "refresh_token_reused",
+            "Refresh token was already used; this login has been revoked",
+        ),
+        Err(error) => error,
+    }
+}
+
+/// Ends the refresh token's session. Unknown tokens are accepted so that
+/// logging out twice is harmless.
+#[post("/logout", data = "<request>")]
+pub async fn logout(
+    request: ValidatedJson<RefreshRequest>,
     db: &State<DatabaseConnection>,
-    config: &State<Config>,
-) -> Result<Json<AuthResponse>, rocket::response::status::BadRequest<Json<ErrorResponse>>> {
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<RefreshToken> = db.database.collection("refresh_tokens");
+
+    let token = collection
+        .find_one(doc! {"token_hash": hash_token(&request.refresh_token)})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch refresh token"))?;
+    if let Some(token) = token {
+        revoke_sessions(doc! {"_id": token.family_id}, db).await?;
+    }
+
+    Ok(Json(serde_json::json!({"message": "Logged out"})))
+}
+
+/// Inserts a new account and seeds its default views.
+pub async fn create_user(mut user: User, db: &DatabaseConnection) -> Result<User, ApiError> {
     let collection: Collection<User> = db.database.collection("users");
 
-    // Find user by email
-    let user = collection.find_one(doc! {"email": &request.email}).await;
-
-    match user {
-        Ok(Some(user)) => {
-            // Verify password
-            match verify_password(&request.password, &user.password_hash) {
-                Ok(true) => {
-                    let user_id = user.id.unwrap().to_hex();
-
-                    // Create JWT token
-                    let token =
-                        match create_jwt(user_id.clone(), user.email.clone(), &config.jwt_secret) {
-                            Ok(token) => token,
-                            Err(_) => {
-                                return Err(rocket::response::status::BadRequest(Json(
-                                    ErrorResponse {
-                                        error: "Failed to create token".to_string(),
-                                    },
-                                )));
-                            }
-                        };
-
-                    let user_response = UserResponse {
-                        id: user_id,
-                        email: user.email,
-                        name: user.name,
-                        created_at: user.created_at,
-                    };
-
-                    Ok(Json(AuthResponse {
-                        token,
-                        user: user_response,
-                    }))
-                }
-                Ok(false) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                    error: "Invalid credentials".to_string(),
-                }))),
-                Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                    error: "Password verification failed".to_string(),
-                }))),
-            }
+    // The unique index on `email` decides between concurrent signups
+    let result = collection.insert_one(&user).await.map_err(|error| {
+        if is_duplicate_key(&error) {
+            email_taken()
+        } else {
+            ApiError::database("Failed to create user")
         }
-        Ok(None) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Invalid credentials".to_string(),
-        }))),
-        Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Database error".to_string(),
-        }))),
+    })?;
+    let object_id = result.inserted_id.as_object_id().unwrap();
+    user.id = Some(object_id);
+
+    // The account is usable without them
+    let views: Collection<View> = db.database.collection("views");
+    let _ = views
+        .insert_many(View::defaults(object_id, user.created_at))
+        .await;
+
+    Ok(user)
+}
+
+pub fn email_taken() -> ApiError {
+    ApiError::conflict("email_taken", "User with this email already exists")
+}
+
+/// Creates an access token with every scope and stores a new refresh token for
+/// `session_id`.
+pub async fn issue_tokens(
+    user_id: ObjectId,
+    email: &str,
+    session_id: ObjectId,
+    db: &DatabaseConnection,
+    config: &Config,
+    keys: &JwtKeys,
+) -> Result<TokenResponse, ApiError> {
+    let collection: Collection<RefreshToken> = db.database.collection("refresh_tokens");
+
+    let token = create_jwt(
+        user_id.to_hex(),
+        email.to_string(),
+        session_id.to_hex(),
+        all_scopes(),
+        keys,
+        config.access_token_ttl(),
+    )
+    .map_err(|_| ApiError::internal("internal_error", "Failed to create token"))?;
+
+    let refresh_token = generate_token();
+    let now = Utc::now();
+    collection
+        .insert_one(RefreshToken {
+            id: None,
+            user_id,
+            family_id: session_id,
+            token_hash: hash_token(&refresh_token),
+            expires_at: now + config.refresh_token_ttl(),
+            created_at: now,
+            rotated_at: None,
+            revoked_at: None,
+        })
+        .await
+        .map_err(|_| ApiError::database("Failed to store refresh token"))?;
+
+    Ok(TokenResponse {
+        token,
+        refresh_token,
+        expires_in: config.access_token_ttl().num_seconds(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use rocket::{
+        http::{ContentType, Status},
+        local::asynchronous::Client,
+    };
+
+    use super::*;
+    use crate::error::default_catcher;
+
+    fn refresh_token(now: DateTime<Utc>) -> RefreshToken {
+        RefreshToken {
+            id: Some(ObjectId::new()),
+            user_id: ObjectId::new(),
+            family_id: ObjectId::new(),
+            token_hash: hash_token("token"),
+            expires_at: now + Duration::days(30),
+            created_at: now,
+            rotated_at: None,
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn fresh_tokens_can_be_exchanged() {
+        let now = Utc::now();
+        assert_eq!(check_refresh_token(&refresh_token(now), now), Ok(()));
+    }
+
+    #[test]
+    fn rotated_tokens_are_reused() {
+        let now = Utc::now();
+        let token = RefreshToken {
+            rotated_at: Some(now - Duration::minutes(5)),
+            ..refresh_token(now)
+        };
+        assert_eq!(
+            check_refresh_token(&token, now),
+            Err(RefreshRejection::Reused)
+        );
+    }
+
+    #[test]
+    fn expired_and_revoked_tokens_are_invalid() {
+        let now = Utc::now();
+        let expired = RefreshToken {
+            expires_at: now,
+            ..refresh_token(now)
+        };
+        // Already revoked for reuse; replaying it again changes nothing
+        let revoked = RefreshToken {
+            rotated_at: Some(now),
+            revoked_at: Some(now),
+            ..refresh_token(now)
+        };
+        for token in [expired, revoked] {
+            assert_eq!(
+                check_refresh_token(&token, now),
+                Err(RefreshRejection::Invalid)
+            );
+        }
+    }
+
+    async fn exchange(client: &Client, refresh_token: String) -> (Status, serde_json::Value) {
+        let response = client
+            .post("/api/auth/refresh")
+            .header(ContentType::JSON)
+            .body(serde_json::json!({"refresh_token": refresh_token}).to_string())
+            .dispatch()
+            .await;
+        let status = response.status();
+        (status, response.into_json().await.unwrap())
+    }
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`).
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn reusing_a_refresh_token_revokes_the_login() {
+        let config = Config {
+            mongodb_uri: std::env::var("MONGODB_TEST_URI")
+                .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
+            database_name: format!("todo_rust_test_{}", ObjectId::new().to_hex()),
+            jwt_secret: Some("test-secret".to_string()),
+            jwt_algorithm: "HS256".to_string(),
+            ..Config::from_env().unwrap()
+        };
+        let db = DatabaseConnection::new(&config).await.unwrap();
+        let keys = JwtKeys::from_config(&config).unwrap();
+        let client_info = ClientInfo {
+            ip: None,
+            user_agent: None,
+        };
+        let now = Utc::now();
+        let user = create_user(
+            User {
+                id: None,
+                email: "ada@example.com".to_string(),
+                password_hash: String::new(),
+                name: "Ada".to_string(),
+                timezone: None,
+                email_verified: true,
+                mfa: None,
+                is_admin: false,
+                identities: Vec::new(),
+                created_at: now,
+                updated_at: now,
+            },
+            &db,
+        )
+        .await
+        .unwrap();
+        let user_id = user.id.unwrap();
+
+        let login = start_session(user_id, &client_info, &db, &config)
+            .await
+            .unwrap();
+        let first = issue_tokens(user_id, &user.email, login, &db, &config, &keys)
+            .await
+            .unwrap();
+        let other_login = start_session(user_id, &client_info, &db, &config)
+            .await
+            .unwrap();
+        let expiring = issue_tokens(user_id, &user.email, other_login, &db, &config, &keys)
+            .await
+            .unwrap();
+
+        let rocket = rocket::build()
+            .manage(config)
+            .manage(db)
+            .manage(keys)
+            .mount("/api/auth", rocket::routes![refresh])
+            .register("/", rocket::catchers![default_catcher]);
+        let client = Client::untracked(rocket).await.unwrap();
+        let db = client.rocket().state::<DatabaseConnection>().unwrap();
+        let config = client.rocket().state::<Config>().unwrap();
+
+        let (status, second) = exchange(&client, first.refresh_token.clone()).await;
+        assert_eq!(status, Status::Ok);
+
+        // The first token again: the whole login goes, including the new token
+        let (status, body) = exchange(&client, first.refresh_token).await;
+        assert_eq!(status, Status::Unauthorized);
+        assert_eq!(body["code"], "refresh_token_reused");
+        let (status, body) =
+            exchange(&client, second["refresh_token"].as_str().unwrap().into()).await;
+        assert_eq!(status, Status::Unauthorized);
+        assert_eq!(body["code"], "invalid_refresh_token");
+        let error = extend_session(login, &client_info, db, config)
+            .await
+            .unwrap_err();
+        assert_eq!(error.body().code, "session_revoked");
+
+        // An expired token is refused without touching its login
+        db.database
+            .collection::<RefreshToken>("refresh_tokens")
+            .update_one(
+                doc! {"token_hash": hash_token(&expiring.refresh_token)},
+                doc! {"$set": {"expires_at": BsonDateTime::from_chrono(now)}},
+            )
+            .await
+            .unwrap();
+        let (status, body) = exchange(&client, expiring.refresh_token).await;
+        assert_eq!(status, Status::Unauthorized);
+        assert_eq!(body["code"], "invalid_refresh_token");
+        extend_session(other_login, &client_info, db, config)
+            .await
+            .unwrap();
+
+        db.database.drop().await.unwrap();
     }
 }
@@ -0,0 +1,294 @@
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime},
+    Collection,
+};
+use rocket::{delete, post, put, serde::json::Json, State};
+
+use crate::{
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    handlers::{
+        recurrence::spawn_next_occurrence,
+        todo::{parse_todo_id, todo_not_found},
+    },
+    middleware::{
+        auth::{AuthenticatedUser, VerifiedUser},
+        scope::{Scoped, WriteTodos},
+        validated_json::ValidatedJson,
+    },
+    models::todo::{
+        ChecklistItem, CreateChecklistItemRequest, ReorderChecklistRequest, Todo, TodoResponse,
+        UpdateChecklistItemRequest,
+    },
+};
+
+#[post("/todos/<id>/items", data = "<request>")]
+pub async fn add_item(
+    id: String,
+    request: ValidatedJson<CreateChecklistItemRequest>,
+    user: Scoped<WriteTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoResponse>, ApiError> {
+    let mut todo = load_todo(&id, &user, db).await?;
+    let loaded_items = todo.items.clone();
+
+    let text = request.text.trim().to_string();
+
+    let position = request
+        .position
+        .map(|position| (position as usize).min(todo.items.len()))
+        .unwrap_or(todo.items.len());
+    todo.items.insert(
+        position,
+        ChecklistItem {
+            id: ObjectId::new(),
+            text,
+            done: false,
+            position: 0, // renumbered on save
+        },
+    );
+
+    Ok(Json(save_items(todo, &loaded_items, db).await?))
+}
+
+#[put("/todos/<id>/items", data = "<request>")]
+pub async fn reorder_items(
+    id: String,
+    request: ValidatedJson<ReorderChecklistRequest>,
+    user: Scoped<WriteTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoResponse>, ApiError> {
+    let mut todo = load_todo(&id, &user, db).await?;
+    let loaded_items = todo.items.clone();
+
+    let incomplete =
+        || ApiError::validation("item_ids", "item_ids must list every item exactly once");
+    if request.item_ids.len() != todo.items.len() {
+        return Err(incomplete());
+    }
+
+    let mut reordered = Vec::with_capacity(todo.items.len());
+    for item_id in &request.item_ids {
+        let item_id = ObjectId::parse_str(item_id).map_err(|_| {
+            ApiError::validation("item_ids", format!("Invalid item ID: {}", item_id))
+        })?;
+        let index = todo
+            .items
+            .iter()
+            .position(|item| item.id == item_id)
+            .ok_or_else(incomplete)?;
+        reordered.push(todo.items.remove(index));
+    }
+    todo.items = reordered;
+
+    Ok(Json(save_items(todo, &loaded_items, db).await?))
+}
+
+#[put("/todos/<id>/items/<item_id>", data = "<request>")]
+pub async fn update_item(
+    id: String,
+    item_id: String,
+    request: ValidatedJson<UpdateChecklistItemRequest>,
+    user: Scoped<WriteTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoResponse>, ApiError> {
+    let mut todo = load_todo(&id, &user, db).await?;
+    let loaded_items = todo.items.clone();
+
+    let item = find_item(&mut todo, &item_id)?;
+
+    if let Some(text) = &request.text {
+        item.text = text.trim().to_string();
+    }
+    if let Some(done) = request.done {
+        item.done = done;
+    }
+
+    Ok(Json(save_items(todo, &loaded_items, db).await?))
+}
+
+#[post("/todos/<id>/items/<item_id>/toggle")]
+pub async fn toggle_item(
+    id: String,
+    item_id: String,
+    user: Scoped<WriteTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoResponse>, ApiError> {
+    let mut todo = load_todo(&id, &user, db).await?;
+    let loaded_items = todo.items.clone();
+
+    let item = find_item(&mut todo, &item_id)?;
+    item.done = !item.done;
+
+    Ok(Json(save_items(todo, &loaded_items, db).await?))
+}
+
+#[delete("/todos/<id>/items/<item_id>")]
+pub async fn delete_item(
+    id: String,
+    item_id: String,
+    user: Scoped<WriteTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoResponse>, ApiError> {
+    let mut todo = load_todo(&id, &user, db).await?;
+    let loaded_items = todo.items.clone();
+
+    let item_id = find_item(&mut todo, &item_id)?.id;
+    todo.items.retain(|item| item.id != item_id);
+
+    Ok(Json(save_items(todo, &loaded_items, db).await?))
+}
+
+async fn load_todo(
+    id: &str,
+    user: &AuthenticatedUser,
+    db: &DatabaseConnection,
+) -> Result<Todo, ApiError> {
+    let collection: Collection<Todo> = db.database.collection("todos");
+
+    let todo_id = parse_todo_id(id)?;
+    let user_id = user.object_id()?;
+
+    collection
+        .find_one(doc! {"_id": todo_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch todo"))?
+        .ok_or_else(todo_not_found)
+}
+
+fn find_item<'a>(todo: &'a mut Todo, item_id: &str) -> Result<&'a mut ChecklistItem, ApiError> {
+    let item_id = ObjectId::parse_str(item_id)
+        .map_err(|_| ApiError::bad_request("invalid_id", "Invalid item ID"))?;
+    todo.items
+        .iter_mut()
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| ApiError::not_found("item_not_found", "Item not found"))
+}
+
+/// Renumbers the checklist, applies auto-completion and writes the items back.
+/// The write only goes through while the stored items are still
+/// `loaded_items`, so concurrent edits are not lost; the loser gets a 409.
+async fn save_items(
+    mut todo: Todo,
+    loaded_items: &[ChecklistItem],
+    db: &DatabaseConnection,
+) -> Result<TodoResponse, ApiError> {
+    let collection: Collection<Todo> = db.database.collection("todos");
+
+    for (position, item) in todo.items.iter_mut().enumerate() {
+        item.position = position as u32;
+    }
+    todo.updated_at = Utc::now();
+
+    let items = mongodb::bson::to_bson(&todo.items)
+        .map_err(|_| ApiError::internal("internal_error", "Invalid items"))?;
+    let mut set = doc! {
+        "items": items,
+        "updated_at": mongodb::bson::to_bson(&todo.updated_at).unwrap(),
+    };
+    if todo.auto_complete && !todo.items.is_empty() && todo.items.iter().all(|item| item.done) {
+        todo.completed = true;
+        todo.completed_at = todo.completed_at.or(Some(todo.updated_at));
+        set.insert("completed", true);
+        set.insert(
+            "completed_at",
+            todo.completed_at.map(BsonDateTime::from_chrono),
+        );
+    }
+
+    let filter = doc! {"_id": todo.id, "user_id": todo.user_id};
+    let mut guarded = filter.clone();
+    if loaded_items.is_empty() {
+        // Todos created before checklists existed have no `items` at all
+        guarded.insert(
+            "$or",
+            vec![doc! {"items": {"$exists": false}}, doc! {"items": []}],
+        );
+    } else {
+        let loaded_items = mongodb::bson::to_bson(loaded_items)
+            .map_err(|_| ApiError::internal("internal_error", "Invalid items"))?;
+        guarded.insert("items", loaded_items);
+    }
+    let result = collection
+        .update_one(guarded, doc! {"$set": set})
+        .await
+        .map_err(|_| ApiError::database("Failed to update checklist"))?;
+
+    if result.matched_count == 0 {
+        let exists = collection
+            .count_documents(filter)
+            .await
+            .map_err(|_| ApiError::database("Failed to update checklist"))?;
+        return Err(if exists == 0 {
+            todo_not_found()
+        } else {
+            ApiError::conflict(
+                "checklist_changed",
+                "The checklist was changed by another request; reload and try again",
+            )
+        });
+    }
+
+    if let Some(next_id) = spawn_next_occurrence(&todo, db).await? {
+        todo.next_occurrence_id = Some(next_id);
+    }
+    Ok(TodoResponse::from(todo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use mongodb::bson::Document;
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`).
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn saves_items_on_todos_stored_without_them() {
+        let config = Config {
+            mongodb_uri: std::env::var("MONGODB_TEST_URI")
+                .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
+            database_name: format!("todo_rust_test_{}", ObjectId::new().to_hex()),
+            ..Config::from_env().unwrap()
+        };
+        let db = DatabaseConnection::new(&config).await.unwrap();
+        let raw: Collection<Document> = db.database.collection("todos");
+        let todos: Collection<Todo> = db.database.collection("todos");
+        let now = mongodb::bson::to_bson(&Utc::now()).unwrap();
+        let todo_id = raw
+            .insert_one(doc! {
+                "title": "Pack",
+                "description": null,
+                "completed": false,
+                "user_id": ObjectId::new(),
+                "created_at": now.clone(),
+                "updated_at": now,
+            })
+            .await
+            .unwrap()
+            .inserted_id;
+
+        let mut todo = todos
+            .find_one(doc! {"_id": &todo_id})
+            .await
+            .unwrap()
+            .unwrap();
+        let loaded_items = todo.items.clone();
+        todo.items.push(ChecklistItem {
+            id: ObjectId::new(),
+            text: "Socks".to_string(),
+            done: false,
+            position: 0,
+        });
+        let saved = save_items(todo.clone(), &loaded_items, &db).await.unwrap();
+        assert_eq!(saved.items.len(), 1);
+
+        // The stale copy no longer matches
+        let error = save_items(todo, &loaded_items, &db).await.unwrap_err();
+        assert_eq!(error.body().code, "checklist_changed");
+
+        db.database.drop().await.unwrap();
+    }
+}
@@ -0,0 +1,11 @@
+use jsonwebtoken::jwk::JwkSet;
+use rocket::{get, serde::json::Json, State};
+
+use crate::utils::jwt::JwtKeys;
+
+/// The public keys access tokens are signed with, so that other services can
+/// verify them. Empty while tokens are signed with `JWT_SECRET`.
+#[get("/.well-known/jwks.json")]
+pub fn jwks(keys: &State<JwtKeys>) -> Json<JwkSet> {
+    Json(keys.jwks())
+}
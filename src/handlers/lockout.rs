@@ -0,0 +1,169 @@
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime},
+    options::ReturnDocument,
+    Collection,
+};
+use rocket::{post, serde::json::Json, State};
+
+use crate::{
+    config::Config,
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    middleware::{
+        auth::AdminUser,
+        scope::{ManageAccount, Scoped},
+    },
+    models::login_attempt::{lockout_seconds, LoginAttempts},
+    models::user::User,
+};
+
+/// How long failed logins are remembered after the last one.
+const FAILURE_MEMORY_HOURS: i64 = 24;
+
+/// Lifts the lockout of an account and forgets its failed logins.
+#[post("/admin/users/<id>/unlock")]
+pub async fn unlock_user(
+    id: String,
+    _admin: Scoped<ManageAccount, AdminUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let users: Collection<User> = db.database.collection("users");
+    let user_id = ObjectId::parse_str(&id)
+        .map_err(|_| ApiError::bad_request("invalid_id", "Invalid user ID"))?;
+
+    let user = users
+        .find_one(doc! {"_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .ok_or_else(|| ApiError::not_found("user_not_found", "User not found"))?;
+
+    clear_failed_logins(&user.email, db).await?;
+    Ok(Json(serde_json::json!({"message": "Account unlocked"})))
+}
+
+/// Rejects the login with 429 while the account or the client's IP address is
+/// locked out.
+pub async fn check_lockout(
+    email: &str,
+    ip: Option<&str>,
+    db: &DatabaseConnection,
+) -> Result<(), ApiError> {
+    let collection: Collection<LoginAttempts> = db.database.collection("login_attempts");
+
+    let now = Utc::now();
+    let mut cursor = collection
+        .find(doc! {
+            "key": {"$in": lockout_keys(email, ip)},
+            "locked_until": {"$gt": BsonDateTime::from_chrono(now)},
+        })
+        .await
+        .map_err(|_| ApiError::database("Failed to check login attempts"))?;
+
+    let mut locked_until = None;
+    while cursor.advance().await.unwrap_or(false) {
+        if let Ok(attempts) = cursor.deserialize_current() {
+            locked_until = locked_until.max(attempts.locked_until);
+        }
+    }
+
+    match locked_until {
+        Some(until) => Err(ApiError::too_many_requests(
+            "too_many_attempts",
+            "Too many failed logins; try again later",
+            (until - now).num_seconds().max(0) as u64 + 1,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Counts a failed login against the account and the client's IP address,
+/// locking either out once it has too many.
+pub async fn record_failed_login(
+    email: &str,
+    ip: Option<&str>,
+    db: &DatabaseConnection,
+    config: &Config,
+) -> Result<(), ApiError> {
+    record_failure(
+        LoginAttempts::account_key(email),
+        config.login_max_failures,
+        db,
+        config,
+    )
+    .await?;
+    if let Some(ip) = ip {
+        record_failure(
+            LoginAttempts::ip_key(ip),
+            config.login_ip_max_failures,
+            db,
+            config,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Forgets the account's failed logins, after a successful login or an
+/// unlock. Failures from the IP address still count.
+pub async fn clear_failed_logins(email: &str, db: &DatabaseConnection) -> Result<(), ApiError> {
+    let collection: Collection<LoginAttempts> = db.database.collection("login_attempts");
+
+    collection
+        .delete_one(doc! {"key": LoginAttempts::account_key(email)})
+        .await
+        .map_err(|_| ApiError::database("Failed to reset login attempts"))?;
+    Ok(())
+}
+
+async fn record_failure(
+    key: String,
+    allowed: u32,
+    db: &DatabaseConnection,
+    config: &Config,
+) -> Result<(), ApiError> {
+    let collection: Collection<LoginAttempts> = db.database.collection("login_attempts");
+
+    let now = Utc::now();
+    let forget_at = now + Duration::hours(FAILURE_MEMORY_HOURS);
+    let attempts = collection
+        .find_one_and_update(
+            doc! {"key": &key},
+            doc! {
+                "$inc": {"failures": 1},
+                "$set": {"expires_at": BsonDateTime::from_chrono(forget_at)},
+            },
+        )
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|_| ApiError::database("Failed to record login attempt"))?
+        .ok_or_else(|| ApiError::database("Failed to record login attempt"))?;
+
+    let lockout = lockout_seconds(
+        attempts.failures,
+        allowed,
+        config.login_lockout_seconds,
+        config.login_max_lockout_minutes * 60,
+    );
+    if let Some(seconds) = lockout {
+        let until = now + Duration::seconds(seconds as i64);
+        collection
+            .update_one(
+                doc! {"_id": attempts.id},
+                doc! {"$set": {
+                    "locked_until": BsonDateTime::from_chrono(until),
+                    "expires_at": BsonDateTime::from_chrono(forget_at.max(until)),
+                }},
+            )
+            .await
+            .map_err(|_| ApiError::database("Failed to record login attempt"))?;
+    }
+    Ok(())
+}
+
+fn lockout_keys(email: &str, ip: Option<&str>) -> Vec<String> {
+    let mut keys = vec![LoginAttempts::account_key(email)];
+    keys.extend(ip.map(LoginAttempts::ip_key));
+    keys
+}
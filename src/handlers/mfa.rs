@@ -0,0 +1,399 @@
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson},
+    Collection,
+};
+use rocket::{post, serde::json::Json, State};
+
+use crate::{
+    config::Config,
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    handlers::{
+        account::{check_password, load_account},
+        auth::issue_tokens,
+        lockout::{check_lockout, clear_failed_logins, record_failed_login},
+        one_time_token::{
+            consume_one_time_token, find_one_time_token, issue_one_time_token,
+            record_failed_attempt,
+        },
+        session::start_session,
+    },
+    middleware::{
+        auth::AuthenticatedUser,
+        client::ClientInfo,
+        scope::{ManageAccount, Scoped},
+        validated_json::ValidatedJson,
+    },
+    models::mfa::{
+        ConfirmMfaRequest, EnrollMfaRequest, MfaChallenge, MfaEnrollment, MfaLoginRequest,
+        MfaReauthRequest, MfaSettings, RecoveryCodes,
+    },
+    models::one_time_token::TokenPurpose,
+    models::user::{AuthResponse, User, UserResponse},
+    utils::{
+        jwt::JwtKeys,
+        mfa::{
+            decrypt_secret, encrypt_secret, generate_recovery_codes, generate_secret,
+            normalize_recovery_code, totp, verify_code,
+        },
+        password::Passwords,
+        token::hash_token,
+    },
+};
+
+/// How long the second login step may take.
+const MFA_CHALLENGE_TTL_MINUTES: i64 = 5;
+
+/// Starts MFA enrollment. The secret only takes effect once a code from it is
+/// confirmed; enrolling again before that replaces it.
+#[post("/me/mfa", data = "<request>")]
+pub async fn enroll_mfa(
+    request: ValidatedJson<EnrollMfaRequest>,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    passwords: &State<Passwords>,
+) -> Result<Json<MfaEnrollment>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let account = check_password(&user, &request.password, "password", db, passwords).await?;
+    if account.mfa_enabled() {
+        return Err(ApiError::conflict(
+            "mfa_already_enabled",
+            "Two-factor authentication is already enabled",
+        ));
+    }
+
+    let secret = generate_secret();
+    let encrypted = encrypt_secret(&mfa_key(config)?, &secret)
+        .map_err(|_| ApiError::internal("internal_error", "Failed to encrypt secret"))?;
+    let totp = totp(secret, &config.mfa_issuer, &account.email)
+        .map_err(|_| ApiError::internal("internal_error", "Failed to create secret"))?;
+
+    let settings = MfaSettings {
+        secret: encrypted,
+        enabled: false,
+        recovery_codes: Vec::new(),
+        last_used_step: None,
+        enabled_at: None,
+    };
+    collection
+        .update_one(
+            doc! {"_id": account.id, "mfa.enabled": {"$ne": true}},
+            doc! {"$set": {
+                "mfa": to_bson(&settings).unwrap(),
+                "updated_at": to_bson(&Utc::now()).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to store secret"))?;
+
+    Ok(Json(MfaEnrollment {
+        secret: totp.get_secret_base32(),
+        otpauth_uri: totp.get_url(),
+    }))
+}
+
+/// Enables MFA with a first code from the authenticator app and returns the
+/// recovery codes.
+#[post("/me/mfa/confirm", data = "<request>")]
+pub async fn confirm_mfa(
+    request: ValidatedJson<ConfirmMfaRequest>,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+) -> Result<Json<RecoveryCodes>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let account = load_account(&user, db).await?;
+    let settings = match &account.mfa {
+        Some(settings) if !settings.enabled => settings,
+        Some(_) => {
+            return Err(ApiError::conflict(
+                "mfa_already_enabled",
+                "Two-factor authentication is already enabled",
+            ))
+        }
+        None => {
+            return Err(ApiError::conflict(
+                "mfa_not_enrolled",
+                "Start two-factor enrollment first",
+            ))
+        }
+    };
+
+    let totp = account_totp(&account, settings, config)?;
+    let step = verify_code(&totp, &request.code, unix_now(), None)
+        .ok_or_else(|| ApiError::validation("code", "Code is incorrect"))?;
+
+    let codes = generate_recovery_codes();
+    let result = collection
+        .update_one(
+            doc! {"_id": account.id, "mfa.secret": &settings.secret, "mfa.enabled": false},
+            doc! {"$set": {
+                "mfa.enabled": true,
+                "mfa.recovery_codes": hash_recovery_codes(&codes),
+                "mfa.last_used_step": step as i64,
+                "mfa.enabled_at": to_bson(&Utc::now()).unwrap(),
+                "updated_at": to_bson(&Utc::now()).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to enable two-factor authentication"))?;
+    if result.matched_count == 0 {
+        return Err(ApiError::conflict(
+            "mfa_not_enrolled",
+            "Enrollment changed; start again",
+        ));
+    }
+
+    Ok(Json(RecoveryCodes {
+        recovery_codes: codes,
+    }))
+}
+
+/// Turns MFA off. Requires the password and a current code.
+#[post("/me/mfa/disable", data = "<request>")]
+pub async fn disable_mfa(
+    request: ValidatedJson<MfaReauthRequest>,
+    user: Scoped<ManageAccount>,
+    client: ClientInfo,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    passwords: &State<Passwords>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let account = reauthenticate(&user, &request, &client, db, config, passwords).await?;
+
+    collection
+        .update_one(
+            doc! {"_id": account.id},
+            doc! {
+                "$unset": {"mfa": ""},
+                "$set": {"updated_at": to_bson(&Utc::now()).unwrap()},
+            },
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to disable two-factor authentication"))?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Two-factor authentication disabled"
+    })))
+}
+
+/// Replaces all recovery codes. Requires the password and a current code.
+#[post("/me/mfa/recovery-codes", data = "<request>")]
+pub async fn regenerate_recovery_codes(
+    request: ValidatedJson<MfaReauthRequest>,
+    user: Scoped<ManageAccount>,
+    client: ClientInfo,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    passwords: &State<Passwords>,
+) -> Result<Json<RecoveryCodes>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let account = reauthenticate(&user, &request, &client, db, config, passwords).await?;
+
+    let codes = generate_recovery_codes();
+    collection
+        .update_one(
+            doc! {"_id": account.id, "mfa.enabled": true},
+            doc! {"$set": {
+                "mfa.recovery_codes": hash_recovery_codes(&codes),
+                "updated_at": to_bson(&Utc::now()).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to store recovery codes"))?;
+
+    Ok(Json(RecoveryCodes {
+        recovery_codes: codes,
+    }))
+}
+
+/// Second login step: exchanges the challenge from `login` and a TOTP or
+/// recovery code for tokens. A challenge stops working after a few wrong codes.
+#[post("/mfa", data = "<request>")]
+pub async fn verify_mfa(
+    request: ValidatedJson<MfaLoginRequest>,
+    client: ClientInfo,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    keys: &State<JwtKeys>,
+) -> Result<Json<AuthResponse>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+    let invalid = || {
+        ApiError::unauthorized(
+            "invalid_mfa_token",
+            "Invalid or expired two-factor challenge; log in again",
+        )
+    };
+
+    let challenge = find_one_time_token(&request.mfa_token, TokenPurpose::MfaChallenge, db)
+        .await?
+        .ok_or_else(invalid)?;
+    let user = collection
+        .find_one(doc! {"_id": challenge.user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .ok_or_else(invalid)?;
+
+    // Wrong codes count as failed logins, so fresh challenges do not give
+    // code guessers fresh attempts
+    let ip = client.ip.as_deref();
+    check_lockout(&user.email, ip, db).await?;
+    if !check_mfa_code(&user, &request.code, db, config).await? {
+        record_failed_attempt(challenge.id.unwrap(), db).await?;
+        record_failed_login(&user.email, ip, db, config).await?;
+        return Err(ApiError::unauthorized(
+            "invalid_mfa_code",
+            "Invalid two-factor code",
+        ));
+    }
+
+    // Another request may have finished this challenge meanwhile
+    consume_one_time_token(&request.mfa_token, TokenPurpose::MfaChallenge, db)
+        .await?
+        .ok_or_else(invalid)?;
+    clear_failed_logins(&user.email, db).await?;
+
+    let user_id = user.id.unwrap();
+    let session_id = start_session(user_id, &client, db, config).await?;
+    let tokens = issue_tokens(user_id, &user.email, session_id, db, config, keys).await?;
+
+    Ok(Json(AuthResponse {
+        tokens,
+        user: UserResponse::from(user),
+    }))
+}
+
+/// Issues the challenge `login` returns to accounts with MFA enabled.
+pub async fn mfa_challenge(
+    user_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<MfaChallenge, ApiError> {
+    let ttl = Duration::minutes(MFA_CHALLENGE_TTL_MINUTES);
+    let mfa_token =
+        issue_one_time_token(user_id, TokenPurpose::MfaChallenge, None, ttl, db).await?;
+
+    Ok(MfaChallenge {
+        mfa_required: true,
+        mfa_token,
+        expires_in: ttl.num_seconds(),
+    })
+}
+
+/// Checks the password and a code for changes to enabled MFA. Wrong answers
+/// count as failed logins and lock the account out like wrong passwords do.
+async fn reauthenticate(
+    user: &AuthenticatedUser,
+    request: &MfaReauthRequest,
+    client: &ClientInfo,
+    db: &DatabaseConnection,
+    config: &Config,
+    passwords: &Passwords,
+) -> Result<User, ApiError> {
+    let account = load_account(user, db).await?;
+    let ip = client.ip.as_deref();
+    check_lockout(&account.email, ip, db).await?;
+
+    let valid = passwords
+        .verify(&request.password, &account.password_hash)
+        .map_err(|_| ApiError::internal("internal_error", "Password verification failed"))?;
+    if !valid {
+        record_failed_login(&account.email, ip, db, config).await?;
+        return Err(ApiError::validation("password", "Password is incorrect"));
+    }
+    if !account.mfa_enabled() {
+        return Err(ApiError::conflict(
+            "mfa_not_enabled",
+            "Two-factor authentication is not enabled",
+        ));
+    }
+    if !check_mfa_code(&account, &request.code, db, config).await? {
+        record_failed_login(&account.email, ip, db, config).await?;
+        return Err(ApiError::validation("code", "Code is incorrect"));
+    }
+    clear_failed_logins(&account.email, db).await?;
+    Ok(account)
+}
+
+/// Accepts a 6-digit TOTP code or an unused recovery code. Both are spent
+/// atomically, so the same code cannot be used twice.
+async fn check_mfa_code(
+    user: &User,
+    code: &str,
+    db: &DatabaseConnection,
+    config: &Config,
+) -> Result<bool, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+    let settings = match &user.mfa {
+        Some(settings) if settings.enabled => settings,
+        _ => return Ok(false),
+    };
+
+    let code = code.trim();
+    let update = if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        let totp = account_totp(user, settings, config)?;
+        let last_used_step = settings.last_used_step.map(|step| step as u64);
+        let Some(step) = verify_code(&totp, code, unix_now(), last_used_step) else {
+            return Ok(false);
+        };
+        collection.update_one(
+            doc! {
+                "_id": user.id,
+                "mfa.enabled": true,
+                "$or": [
+                    {"mfa.last_used_step": null},
+                    {"mfa.last_used_step": {"$lt": step as i64}},
+                ],
+            },
+            doc! {"$set": {"mfa.last_used_step": step as i64}},
+        )
+    } else {
+        let hash = hash_token(&normalize_recovery_code(code));
+        collection.update_one(
+            doc! {"_id": user.id, "mfa.enabled": true, "mfa.recovery_codes": &hash},
+            doc! {"$pull": {"mfa.recovery_codes": &hash}},
+        )
+    };
+
+    let result = update
+        .await
+        .map_err(|_| ApiError::database("Failed to verify code"))?;
+    Ok(result.matched_count == 1)
+}
+
+fn account_totp(
+    user: &User,
+    settings: &MfaSettings,
+    config: &Config,
+) -> Result<totp_rs::TOTP, ApiError> {
+    let secret = decrypt_secret(&mfa_key(config)?, &settings.secret)
+        .map_err(|_| ApiError::internal("internal_error", "Failed to decrypt secret"))?;
+    totp(secret, &config.mfa_issuer, &user.email)
+        .map_err(|_| ApiError::internal("internal_error", "Failed to load secret"))
+}
+
+fn mfa_key(config: &Config) -> Result<[u8; 32], ApiError> {
+    config.mfa_key().ok_or_else(|| {
+        ApiError::internal(
+            "mfa_not_configured",
+            "Two-factor authentication is not configured",
+        )
+    })
+}
+
+fn hash_recovery_codes(codes: &[String]) -> Vec<String> {
+    codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect()
+}
+
+fn unix_now() -> u64 {
+    Utc::now().timestamp() as u64
+}
@@ -1,2 +1,18 @@
+pub mod account;
+pub mod api_token;
 pub mod auth;
+pub mod checklist;
+pub mod jwks;
+pub mod lockout;
+pub mod mfa;
+pub mod oidc;
+pub mod one_time_token;
+pub mod password_reset;
+pub mod project;
+pub mod recurrence;
+pub mod search;
+pub mod session;
+pub mod tag;
 pub mod todo;
+pub mod verification;
+pub mod view;
@@ -0,0 +1,246 @@
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, to_bson, DateTime as BsonDateTime},
+    Collection,
+};
+use rocket::{post, serde::json::Json, State};
+
+use crate::{
+    config::Config,
+    database::connection::{is_duplicate_key, DatabaseConnection},
+    error::ApiError,
+    handlers::{
+        auth::{create_user, issue_tokens},
+        mfa::mfa_challenge,
+        session::start_session,
+    },
+    middleware::{client::ClientInfo, validated_json::ValidatedJson},
+    models::mfa::LoginResponse,
+    models::oidc::{LinkedIdentity, OidcAuthorization, OidcCallbackRequest, OidcLogin},
+    models::user::{AuthResponse, User, UserResponse},
+    oidc::{IdentityClaims, OidcProvider, OidcProviders},
+    utils::{
+        jwt::JwtKeys,
+        password::Passwords,
+        token::{generate_token, hash_token},
+    },
+};
+
+/// How long the user has to sign in at the provider.
+const OIDC_LOGIN_TTL_MINUTES: i64 = 10;
+
+/// Starts a sign-in with `provider`. The client sends the user to
+/// `authorization_url`, then posts the `code` and `state` from the redirect
+/// back to `/oidc/<provider>/callback`.
+#[post("/oidc/<provider>")]
+pub async fn start_oidc_login(
+    provider: &str,
+    db: &State<DatabaseConnection>,
+    providers: &State<OidcProviders>,
+) -> Result<Json<OidcAuthorization>, ApiError> {
+    let collection: Collection<OidcLogin> = db.database.collection("oidc_logins");
+    let oidc = find_provider(provider, providers)?;
+
+    let state = generate_token();
+    let login = OidcLogin {
+        id: None,
+        provider: provider.to_string(),
+        state_hash: hash_token(&state),
+        code_verifier: generate_token(),
+        nonce: generate_token(),
+        expires_at: Utc::now() + Duration::minutes(OIDC_LOGIN_TTL_MINUTES),
+    };
+    let authorization_url = oidc
+        .authorization_url(&state, &login.nonce, &login.code_verifier)
+        .await
+        .map_err(|error| {
+            eprintln!("Sign-in with {} failed: {}", provider, error);
+            ApiError::internal(
+                "provider_unavailable",
+                "The sign-in provider could not be reached",
+            )
+        })?;
+
+    collection
+        .insert_one(&login)
+        .await
+        .map_err(|_| ApiError::database("Failed to start sign-in"))?;
+
+    Ok(Json(OidcAuthorization {
+        authorization_url,
+        state,
+        expires_in: OIDC_LOGIN_TTL_MINUTES * 60,
+    }))
+}
+
+/// Finishes a sign-in with the code from the provider. Signs in the account
+/// linked to the provider account, else the account with the same (verified)
+/// email, which then gets linked, else a new account. As with `login`,
+/// accounts with MFA enabled get a challenge instead of tokens.
+#[post("/oidc/<provider>/callback", data = "<request>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn finish_oidc_login(
+    provider: &str,
+    request: ValidatedJson<OidcCallbackRequest>,
+    client: ClientInfo,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    keys: &State<JwtKeys>,
+    passwords: &State<Passwords>,
+    providers: &State<OidcProviders>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let collection: Collection<OidcLogin> = db.database.collection("oidc_logins");
+    let oidc = find_provider(provider, providers)?;
+
+    // Deleted on first use, so a state cannot be replayed
+    let login = collection
+        .find_one_and_delete(doc! {
+            "provider": provider,
+            "state_hash": hash_token(&request.state),
+            "expires_at": {"$gt": BsonDateTime::from_chrono(Utc::now())},
+        })
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch sign-in"))?
+        .ok_or_else(|| {
+            ApiError::bad_request(
+                "invalid_state",
+                "Sign-in expired or was already finished; start again",
+            )
+        })?;
+
+    let claims = oidc
+        .exchange_code(&request.code, &login.code_verifier, &login.nonce)
+        .await
+        .map_err(|error| {
+            eprintln!("Sign-in with {} failed: {}", provider, error);
+            ApiError::unauthorized(
+                "oidc_login_failed",
+                "The sign-in provider did not confirm the sign-in",
+            )
+        })?;
+
+    let user = find_or_create_user(provider, claims, db, passwords).await?;
+    let object_id = user.id.unwrap();
+
+    if user.mfa_enabled() {
+        let challenge = mfa_challenge(object_id, db).await?;
+        return Ok(Json(LoginResponse::MfaRequired(challenge)));
+    }
+
+    let session_id = start_session(object_id, &client, db, config).await?;
+    let tokens = issue_tokens(object_id, &user.email, session_id, db, config, keys).await?;
+
+    Ok(Json(LoginResponse::Authenticated(AuthResponse {
+        tokens,
+        user: UserResponse::from(user),
+    })))
+}
+
+fn find_provider<'a>(
+    name: &str,
+    providers: &'a OidcProviders,
+) -> Result<&'a OidcProvider, ApiError> {
+    providers
+        .get(name)
+        .ok_or_else(|| ApiError::not_found("unknown_provider", "Unknown sign-in provider"))
+}
+
+async fn find_or_create_user(
+    provider: &str,
+    claims: IdentityClaims,
+    db: &DatabaseConnection,
+    passwords: &Passwords,
+) -> Result<User, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let linked = collection
+        .find_one(doc! {
+            "identities": {"$elemMatch": {"provider": provider, "subject": &claims.sub}},
+        })
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?;
+    if let Some(user) = linked {
+        return Ok(user);
+    }
+
+    let email = match claims.email {
+        Some(email) if claims.email_verified => email,
+        _ => {
+            return Err(ApiError::forbidden(
+                "email_not_verified",
+                "The sign-in provider has no verified email address for this account",
+            ))
+        }
+    };
+    let now = Utc::now();
+    let identity = LinkedIdentity {
+        provider: provider.to_string(),
+        subject: claims.sub,
+        linked_at: now,
+    };
+
+    let existing = collection
+        .find_one(doc! {"email": &email})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?;
+    if let Some(mut user) = existing {
+        // Anyone can sign up with an address they do not own; linking such an
+        // account would leave its password with them
+        if !user.email_verified {
+            return Err(ApiError::conflict(
+                "account_not_verified",
+                "Verify this account's email before signing in to it with a provider",
+            ));
+        }
+        // One account per provider; a second one still signs in by email
+        collection
+            .update_one(
+                doc! {"_id": user.id, "identities.provider": {"$ne": provider}},
+                doc! {
+                    "$push": {"identities": to_bson(&identity).unwrap()},
+                    "$set": {"updated_at": to_bson(&now).unwrap()},
+                },
+            )
+            .await
+            .map_err(|error| {
+                if is_duplicate_key(&error) {
+                    ApiError::conflict(
+                        "identity_linked",
+                        "This provider account is already linked to another account",
+                    )
+                } else {
+                    ApiError::database("Failed to link account")
+                }
+            })?;
+        user.identities.push(identity);
+        return Ok(user);
+    }
+
+    // Nobody knows this password; a password reset sets a usable one
+    let password_hash = passwords
+        .hash(&generate_token())
+        .map_err(|_| ApiError::internal("internal_error", "Failed to hash password"))?;
+    let name = claims
+        .name
+        .map(|name| name.trim().chars().take(100).collect::<String>())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| email.split('@').next().unwrap_or_default().to_string());
+
+    create_user(
+        User {
+            id: None,
+            email,
+            password_hash,
+            name,
+            timezone: None,
+            email_verified: true,
+            mfa: None,
+            is_admin: false,
+            identities: vec![identity],
+            created_at: now,
+            updated_at: now,
+        },
+        db,
+    )
+    .await
+}
@@ -0,0 +1,122 @@
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, DateTime as BsonDateTime, Document},
+    Collection,
+};
+
+use crate::{
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    models::one_time_token::{OneTimeToken, TokenPurpose, MAX_TOKEN_ATTEMPTS},
+    utils::token::{generate_token, hash_token},
+};
+
+/// Stores a new token for `purpose` and returns it in plain form, for the
+/// email. Earlier unused tokens of the same purpose stop working. `email` is
+/// the address being confirmed by an email change.
+pub async fn issue_one_time_token(
+    user_id: ObjectId,
+    purpose: TokenPurpose,
+    email: Option<&str>,
+    ttl: Duration,
+    db: &DatabaseConnection,
+) -> Result<String, ApiError> {
+    let collection: Collection<OneTimeToken> = db.database.collection("one_time_tokens");
+
+    invalidate_one_time_tokens(user_id, purpose, db).await?;
+
+    let token = generate_token();
+    let now = Utc::now();
+    collection
+        .insert_one(OneTimeToken {
+            id: None,
+            user_id,
+            purpose,
+            email: email.map(str::to_string),
+            token_hash: hash_token(&token),
+            expires_at: now + ttl,
+            created_at: now,
+            used_at: None,
+            attempts: 0,
+        })
+        .await
+        .map_err(|_| ApiError::database("Failed to store token"))?;
+    Ok(token)
+}
+
+/// Marks a valid token as used and returns it. Returns `None` for unknown,
+/// expired or already used tokens.
+pub async fn consume_one_time_token(
+    token: &str,
+    purpose: TokenPurpose,
+    db: &DatabaseConnection,
+) -> Result<Option<OneTimeToken>, ApiError> {
+    let collection: Collection<OneTimeToken> = db.database.collection("one_time_tokens");
+
+    collection
+        .find_one_and_update(
+            valid_token(token, purpose),
+            doc! {"$set": {"used_at": BsonDateTime::from_chrono(Utc::now())}},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to use token"))
+}
+
+/// Looks up a valid token without using it, for flows that allow a few wrong
+/// answers (see `record_failed_attempt`) before the token is spent.
+pub async fn find_one_time_token(
+    token: &str,
+    purpose: TokenPurpose,
+    db: &DatabaseConnection,
+) -> Result<Option<OneTimeToken>, ApiError> {
+    let collection: Collection<OneTimeToken> = db.database.collection("one_time_tokens");
+
+    collection
+        .find_one(valid_token(token, purpose))
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch token"))
+}
+
+pub async fn record_failed_attempt(
+    token_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<(), ApiError> {
+    let collection: Collection<OneTimeToken> = db.database.collection("one_time_tokens");
+
+    collection
+        .update_one(doc! {"_id": token_id}, doc! {"$inc": {"attempts": 1}})
+        .await
+        .map_err(|_| ApiError::database("Failed to update token"))?;
+    Ok(())
+}
+
+pub async fn invalidate_one_time_tokens(
+    user_id: ObjectId,
+    purpose: TokenPurpose,
+    db: &DatabaseConnection,
+) -> Result<(), ApiError> {
+    let collection: Collection<OneTimeToken> = db.database.collection("one_time_tokens");
+
+    collection
+        .update_many(
+            doc! {"user_id": user_id, "purpose": purpose_bson(purpose), "used_at": null},
+            doc! {"$set": {"used_at": BsonDateTime::from_chrono(Utc::now())}},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to invalidate tokens"))?;
+    Ok(())
+}
+
+fn valid_token(token: &str, purpose: TokenPurpose) -> Document {
+    doc! {
+        "token_hash": hash_token(token),
+        "purpose": purpose_bson(purpose),
+        "used_at": null,
+        "expires_at": {"$gt": BsonDateTime::from_chrono(Utc::now())},
+        "attempts": {"$lt": MAX_TOKEN_ATTEMPTS},
+    }
+}
+
+fn purpose_bson(purpose: TokenPurpose) -> mongodb::bson::Bson {
+    to_bson(&purpose).unwrap()
+}
@@ -0,0 +1,277 @@
+use chrono::Utc;
+use mongodb::{bson::doc, Collection};
+use rocket::{post, serde::json::Json, State};
+
+use crate::{
+    config::Config,
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    handlers::{
+        one_time_token::{consume_one_time_token, issue_one_time_token},
+        session::revoke_sessions,
+    },
+    mail::{send_later, Email, SharedMailer},
+    middleware::validated_json::ValidatedJson,
+    models::api_token::ApiToken,
+    models::one_time_token::{ForgotPasswordRequest, ResetPasswordRequest, TokenPurpose},
+    models::user::User,
+    utils::password::Passwords,
+};
+
+/// Emails a reset link when the address belongs to an account. The response is
+/// the same either way, so the endpoint cannot be used to probe for accounts.
+#[post("/forgot-password", data = "<request>")]
+pub async fn forgot_password(
+    request: ValidatedJson<ForgotPasswordRequest>,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    mailer: &State<SharedMailer>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let user = collection
+        .find_one(doc! {"email": &request.email})
+        .await
+        .map_err(|_| ApiError::database("Database error"))?;
+
+    if let Some(user) = user {
+        let token = issue_one_time_token(
+            user.id.unwrap(),
+            TokenPurpose::PasswordReset,
+            None,
+            config.reset_token_ttl(),
+            db,
+        )
+        .await?;
+
+        send_later(mailer.inner(), reset_email(user.email, &token, config));
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "If an account exists for this email, a reset link has been sent"
+    })))
+}
+
+fn reset_email(to: String, token: &str, config: &Config) -> Email {
+    Email {
+        to,
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Someone asked to reset the password of your account.\n\n\
+             Open this link within {} minutes to choose a new one:\n\
+             {}/reset-password?token={}\n\n\
+             If this wasn't you, ignore this email.",
+            config.reset_token_ttl_minutes, config.app_url, token
+        ),
+    }
+}
+
+/// Sets a new password with a token from the reset email and logs out every
+/// session.
+#[post("/reset-password", data = "<request>")]
+pub async fn reset_password(
+    request: ValidatedJson<ResetPasswordRequest>,
+    db: &State<DatabaseConnection>,
+    passwords: &State<Passwords>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let token = consume_one_time_token(&request.token, TokenPurpose::PasswordReset, db)
+        .await?
+        .ok_or_else(|| {
+            ApiError::bad_request(
+                "invalid_reset_token",
+                "Invalid, expired or already used reset token",
+            )
+        })?;
+
+    let password_hash = passwords
+        .hash(&request.new_password)
+        .map_err(|_| ApiError::internal("internal_error", "Failed to hash password"))?;
+    collection
+        .update_one(
+            doc! {"_id": token.user_id},
+            doc! {"$set": {
+                "password_hash": password_hash,
+                "updated_at": mongodb::bson::to_bson(&Utc::now()).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to update password"))?;
+
+    revoke_sessions(doc! {"user_id": token.user_id}, db).await?;
+    // Whoever knew the old password may have created tokens with it
+    db.database
+        .collection::<ApiToken>("api_tokens")
+        .delete_many(doc! {"user_id": token.user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to revoke tokens"))?;
+
+    Ok(Json(
+        serde_json::json!({"message": "Password has been reset"}),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use mongodb::bson::{oid::ObjectId, DateTime as BsonDateTime};
+    use rocket::{
+        http::{ContentType, Status},
+        local::asynchronous::Client,
+    };
+
+    use super::*;
+    use crate::{
+        error::default_catcher, mail::memory::MemoryMailer, models::one_time_token::OneTimeToken,
+        utils::password::BcryptHasher,
+    };
+
+    fn config() -> Config {
+        Config {
+            mongodb_uri: std::env::var("MONGODB_TEST_URI")
+                .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
+            database_name: format!("todo_rust_test_{}", ObjectId::new().to_hex()),
+            app_url: "https://todo.example.com".to_string(),
+            reset_token_ttl_minutes: 30,
+            ..Config::from_env().unwrap()
+        }
+    }
+
+    fn link_token(email: &Email) -> &str {
+        let link = "https://todo.example.com/reset-password?token=";
+        let start = email.body.find(link).unwrap() + link.len();
+        email.body[start..].split_whitespace().next().unwrap()
+    }
+
+    #[test]
+    fn reset_email_links_to_the_app_with_the_token() {
+        let email = reset_email("ada@example.com".to_string(), "abc123", &config());
+
+        assert_eq!(email.to, "ada@example.com");
+        assert_eq!(email.subject, "Reset your password");
+        assert!(email.body.contains("within 30 minutes"));
+        assert_eq!(link_token(&email), "abc123");
+    }
+
+    async fn wait_for_emails(mailer: &MemoryMailer, count: usize) -> Vec<Email> {
+        for _ in 0..100 {
+            if mailer.sent().len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        mailer.sent()
+    }
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`).
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn resets_the_password_once_with_the_emailed_token() {
+        let config = config();
+        let db = DatabaseConnection::new(&config).await.unwrap();
+        let users: Collection<User> = db.database.collection("users");
+        let tokens: Collection<OneTimeToken> = db.database.collection("one_time_tokens");
+        let passwords = Passwords::new(Box::new(BcryptHasher::new(4)), Vec::new()).unwrap();
+        let now = Utc::now();
+        let user_id = users
+            .insert_one(User {
+                id: None,
+                email: "ada@example.com".to_string(),
+                password_hash: passwords.hash("old-passw0rd").unwrap(),
+                name: "Ada".to_string(),
+                timezone: None,
+                email_verified: true,
+                mfa: None,
+                is_admin: false,
+                identities: Vec::new(),
+                created_at: now,
+                updated_at: now,
+            })
+            .await
+            .unwrap()
+            .inserted_id
+            .as_object_id()
+            .unwrap();
+
+        let memory = Arc::new(MemoryMailer::default());
+        let mailer: SharedMailer = memory.clone();
+        let rocket = rocket::build()
+            .manage(config)
+            .manage(db)
+            .manage(mailer)
+            .manage(passwords)
+            .mount(
+                "/api/auth",
+                rocket::routes![forgot_password, reset_password],
+            )
+            .register("/", rocket::catchers![default_catcher]);
+        let client = Client::untracked(rocket).await.unwrap();
+
+        let forgot = |email: &'static str| {
+            client
+                .post("/api/auth/forgot-password")
+                .header(ContentType::JSON)
+                .body(serde_json::json!({"email": email}).to_string())
+                .dispatch()
+        };
+        let reset = |token: String, password: &'static str| {
+            client
+                .post("/api/auth/reset-password")
+                .header(ContentType::JSON)
+                .body(serde_json::json!({"token": token, "new_password": password}).to_string())
+                .dispatch()
+        };
+
+        // Unknown addresses get the same answer and no email
+        let unknown = forgot("nobody@example.com").await;
+        assert_eq!(unknown.status(), Status::Ok);
+        let unknown = unknown.into_string().await.unwrap();
+        let known = forgot("ada@example.com").await;
+        assert_eq!(known.status(), Status::Ok);
+        assert_eq!(known.into_string().await.unwrap(), unknown);
+
+        let sent = wait_for_emails(&memory, 1).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "ada@example.com");
+        let token = link_token(&sent[0]).to_string();
+
+        let response = reset(token.clone(), "new-passw0rd").await;
+        assert_eq!(response.status(), Status::Ok);
+        let user = users
+            .find_one(doc! {"_id": user_id})
+            .await
+            .unwrap()
+            .unwrap();
+        let passwords = client.rocket().state::<Passwords>().unwrap();
+        assert!(passwords
+            .verify("new-passw0rd", &user.password_hash)
+            .unwrap());
+
+        // Single use
+        let response = reset(token, "other-passw0rd").await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // Expired
+        forgot("ada@example.com").await;
+        let sent = wait_for_emails(&memory, 2).await;
+        let token = link_token(&sent[1]).to_string();
+        tokens
+            .update_many(
+                doc! {"user_id": user_id},
+                doc! {"$set": {"expires_at": BsonDateTime::from_chrono(now)}},
+            )
+            .await
+            .unwrap();
+        let response = reset(token, "other-passw0rd").await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(body["code"], "invalid_reset_token");
+
+        let db = client.rocket().state::<DatabaseConnection>().unwrap();
+        db.database.drop().await.unwrap();
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, Document},
+    Collection,
+};
+use rocket::{delete, get, post, put, serde::json::Json, State};
+
+use crate::{
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    handlers::todo::{find_todos, parse_todo_id, todo_not_found},
+    middleware::{
+        auth::VerifiedUser,
+        scope::{AdminProjects, ReadTodos, Scoped, WriteTodos},
+        validated_json::ValidatedJson,
+    },
+    models::project::{
+        CreateProjectRequest, DeleteMode, MoveTodoRequest, Project, ProjectResponse,
+        UpdateProjectRequest, INBOX_ID,
+    },
+    models::todo::{Todo, TodoPage, TodoQuery, TodoResponse},
+    models::view::View,
+};
+
+#[post("/projects", data = "<request>")]
+pub async fn create_project(
+    request: ValidatedJson<CreateProjectRequest>,
+    user: Scoped<AdminProjects>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<ProjectResponse>, ApiError> {
+    let collection: Collection<Project> = db.database.collection("projects");
+    let user_id = user.object_id()?;
+
+    let name = request.name.trim().to_string();
+
+    let now = Utc::now();
+    let mut project = Project {
+        id: None,
+        user_id,
+        name,
+        created_at: now,
+        updated_at: now,
+    };
+
+    let result = collection
+        .insert_one(&project)
+        .await
+        .map_err(|_| ApiError::database("Failed to create project"))?;
+    project.id = result.inserted_id.as_object_id();
+    Ok(Json(ProjectResponse::from_project(project, 0, 0)))
+}
+
+#[get("/projects")]
+pub async fn get_projects(
+    user: Scoped<ReadTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<Vec<ProjectResponse>>, ApiError> {
+    let collection: Collection<Project> = db.database.collection("projects");
+    let user_id = user.object_id()?;
+
+    let counts = todo_counts(doc! {"user_id": user_id}, db).await?;
+
+    let mut cursor = collection
+        .find(doc! {"user_id": user_id})
+        .sort(doc! {"name": 1})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch projects"))?;
+
+    let (completed, total) = counts.get(&None).copied().unwrap_or_default();
+    let mut projects = vec![ProjectResponse::inbox(completed, total)];
+
+    while cursor.advance().await.unwrap_or(false) {
+        if let Ok(project) = cursor.deserialize_current() {
+            let (completed, total) = counts.get(&project.id).copied().unwrap_or_default();
+            projects.push(ProjectResponse::from_project(project, completed, total));
+        }
+    }
+    Ok(Json(projects))
+}
+
+#[get("/projects/<id>")]
+pub async fn get_project(
+    id: String,
+    user: Scoped<ReadTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<ProjectResponse>, ApiError> {
+    let collection: Collection<Project> = db.database.collection("projects");
+    let user_id = user.object_id()?;
+
+    let project_id = resolve_project_id(&id, user_id, db).await?;
+
+    // `null` also matches Inbox todos stored without a `project_id`
+    let counts = todo_counts(doc! {"user_id": user_id, "project_id": project_id}, db).await?;
+    let (completed, total) = counts.get(&project_id).copied().unwrap_or_default();
+
+    let project_id = match project_id {
+        Some(project_id) => project_id,
+        None => return Ok(Json(ProjectResponse::inbox(completed, total))),
+    };
+
+    collection
+        .find_one(doc! {"_id": project_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch project"))?
+        .map(|project| Json(ProjectResponse::from_project(project, completed, total)))
+        .ok_or_else(project_not_found)
+}
+
+#[put("/projects/<id>", data = "<request>")]
+pub async fn update_project(
+    id: String,
+    request: ValidatedJson<UpdateProjectRequest>,
+    user: Scoped<AdminProjects>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<ProjectResponse>, ApiError> {
+    let collection: Collection<Project> = db.database.collection("projects");
+    let user_id = user.object_id()?;
+
+    let project_id = resolve_project_id(&id, user_id, db)
+        .await?
+        .ok_or_else(|| ApiError::forbidden("inbox_read_only", "The Inbox cannot be modified"))?;
+
+    let mut update_doc = doc! {"updated_at": mongodb::bson::to_bson(&Utc::now()).unwrap()};
+
+    if let Some(name) = &request.name {
+        update_doc.insert("name", name.trim());
+    }
+
+    collection
+        .update_one(
+            doc! {"_id": project_id, "user_id": user_id},
+            doc! {"$set": update_doc},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to update project"))?;
+
+    let counts = todo_counts(doc! {"user_id": user_id, "project_id": project_id}, db).await?;
+    let (completed, total) = counts.get(&Some(project_id)).copied().unwrap_or_default();
+
+    collection
+        .find_one(doc! {"_id": project_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch updated project"))?
+        .map(|project| Json(ProjectResponse::from_project(project, completed, total)))
+        .ok_or_else(project_not_found)
+}
+
+#[delete("/projects/<id>?<mode>")]
+pub async fn delete_project(
+    id: String,
+    mode: Option<DeleteMode>,
+    user: Scoped<AdminProjects>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user_id = user.object_id()?;
+
+    let project_id = resolve_project_id(&id, user_id, db)
+        .await?
+        .ok_or_else(|| ApiError::forbidden("inbox_read_only", "The Inbox cannot be deleted"))?;
+
+    remove_project(user_id, project_id, mode.unwrap_or_default(), db)
+        .await
+        .map_err(|_| ApiError::database("Failed to delete project"))?;
+    Ok(Json(
+        serde_json::json!({"message": "Project deleted successfully"}),
+    ))
+}
+
+/// Deletes a project, then moves or deletes its todos and detaches the views
+/// limited to it. Runs in a transaction where the deployment supports them;
+/// otherwise the project goes first, so that a failure part-way never leaves
+/// a listed project with only some of its todos.
+async fn remove_project(
+    user_id: ObjectId,
+    project_id: ObjectId,
+    mode: DeleteMode,
+    db: &DatabaseConnection,
+) -> mongodb::error::Result<()> {
+    let projects: Collection<Project> = db.database.collection("projects");
+    let todos: Collection<Todo> = db.database.collection("todos");
+    let views: Collection<View> = db.database.collection("views");
+
+    let project_filter = doc! {"_id": project_id, "user_id": user_id};
+    let todo_filter = doc! {"user_id": user_id, "project_id": project_id};
+    // Views limited to the project fall back to all projects
+    let view_filter = doc! {"user_id": user_id, "project": project_id.to_hex()};
+    let view_update = doc! {"$set": {"project": null}};
+    let todo_update = doc! {"$set": {"project_id": null}};
+
+    if !db.supports_transactions {
+        projects.delete_one(project_filter).await?;
+        match mode {
+            DeleteMode::Cascade => {
+                todos.delete_many(todo_filter).await?;
+            }
+            DeleteMode::Inbox => {
+                todos.update_many(todo_filter, todo_update).await?;
+            }
+        }
+        views.update_many(view_filter, view_update).await?;
+        return Ok(());
+    }
+
+    let mut session = db.client.start_session().await?;
+    session.start_transaction().await?;
+    projects
+        .delete_one(project_filter)
+        .session(&mut session)
+        .await?;
+    match mode {
+        DeleteMode::Cascade => {
+            todos.delete_many(todo_filter).session(&mut session).await?;
+        }
+        DeleteMode::Inbox => {
+            todos
+                .update_many(todo_filter, todo_update)
+                .session(&mut session)
+                .await?;
+        }
+    }
+    views
+        .update_many(view_filter, view_update)
+        .session(&mut session)
+        .await?;
+    session.commit_transaction().await
+}
+
+#[get("/projects/<id>/todos?<query..>")]
+pub async fn get_project_todos(
+    id: String,
+    mut query: TodoQuery,
+    user: Scoped<ReadTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoPage>, ApiError> {
+    let user_id = user.object_id()?;
+
+    query.project = Some(id);
+
+    Ok(Json(find_todos(&query, user_id, db).await?))
+}
+
+#[post("/todos/<id>/move", data = "<request>")]
+pub async fn move_todo(
+    id: String,
+    request: ValidatedJson<MoveTodoRequest>,
+    user: Scoped<WriteTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoResponse>, ApiError> {
+    let collection: Collection<Todo> = db.database.collection("todos");
+
+    let todo_id = parse_todo_id(&id)?;
+    let user_id = user.object_id()?;
+
+    let project_id = match &request.project_id {
+        Some(project) => resolve_project_id(project, user_id, db).await?,
+        None => None,
+    };
+
+    let result = collection
+        .update_one(
+            doc! {"_id": todo_id, "user_id": user_id},
+            doc! {"$set": {
+                "project_id": project_id,
+                "updated_at": mongodb::bson::to_bson(&Utc::now()).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to move todo"))?;
+    if result.matched_count == 0 {
+        return Err(todo_not_found());
+    }
+
+    collection
+        .find_one(doc! {"_id": todo_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch moved todo"))?
+        .map(|todo| Json(TodoResponse::from(todo)))
+        .ok_or_else(todo_not_found)
+}
+
+fn project_not_found() -> ApiError {
+    ApiError::not_found("project_not_found", "Project not found")
+}
+
+/// Resolves a project id sent by a client, checking that it belongs to
+/// `user_id`. `"inbox"` resolves to `None`.
+pub async fn resolve_project_id(
+    id: &str,
+    user_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<Option<ObjectId>, ApiError> {
+    if id == INBOX_ID {
+        return Ok(None);
+    }
+
+    let collection: Collection<Project> = db.database.collection("projects");
+    let project_id = ObjectId::parse_str(id)
+        .map_err(|_| ApiError::bad_request("invalid_id", "Invalid project ID"))?;
+
+    match collection
+        .find_one(doc! {"_id": project_id, "user_id": user_id})
+        .await
+    {
+        Ok(Some(_)) => Ok(Some(project_id)),
+        Ok(None) => Err(project_not_found()),
+        Err(_) => Err(ApiError::database("Failed to fetch project")),
+    }
+}
+
+/// Returns `(completed, total)` todo counts per project for the todos matching
+/// `filter`, keyed by `project_id` (`None` for the Inbox).
+async fn todo_counts(
+    filter: Document,
+    db: &DatabaseConnection,
+) -> Result<HashMap<Option<ObjectId>, (u64, u64)>, ApiError> {
+    let todos: Collection<Todo> = db.database.collection("todos");
+
+    let pipeline = vec![
+        doc! {"$match": filter},
+        doc! {"$group": {
+            "_id": {"$ifNull": ["$project_id", null]},
+            "completed": {"$sum": {"$cond": ["$completed", 1, 0]}},
+            "total": {"$sum": 1},
+        }},
+    ];
+
+    let mut cursor = todos
+        .aggregate(pipeline)
+        .await
+        .map_err(|_| ApiError::database("Failed to count todos"))?;
+
+    let mut counts = HashMap::new();
+    while cursor.advance().await.unwrap_or(false) {
+        if let Ok(group) = cursor.deserialize_current() {
+            let project_id = group.get_object_id("_id").ok();
+            counts.insert(
+                project_id,
+                (
+                    count_field(&group, "completed"),
+                    count_field(&group, "total"),
+                ),
+            );
+        }
+    }
+
+    Ok(counts)
+}
+
+fn count_field(group: &Document, key: &str) -> u64 {
+    match group.get(key) {
+        Some(Bson::Int32(count)) => *count as u64,
+        Some(Bson::Int64(count)) => *count as u64,
+        _ => 0,
+    }
+}
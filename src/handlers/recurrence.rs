@@ -0,0 +1,168 @@
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime},
+    Collection,
+};
+use rocket::{post, serde::json::Json, State};
+
+use crate::{
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    handlers::todo::{parse_todo_id, resolve_timezone, todo_not_found},
+    middleware::{
+        auth::VerifiedUser,
+        scope::{Scoped, WriteTodos},
+    },
+    models::todo::{Todo, TodoResponse},
+};
+
+/// Moves an open occurrence to the series' next date without completing it.
+#[post("/todos/<id>/skip")]
+pub async fn skip_occurrence(
+    id: String,
+    user: Scoped<WriteTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoResponse>, ApiError> {
+    let collection: Collection<Todo> = db.database.collection("todos");
+
+    let todo_id = parse_todo_id(&id)?;
+    let user_id = user.object_id()?;
+
+    let mut todo = collection
+        .find_one(doc! {"_id": todo_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch todo"))?
+        .ok_or_else(todo_not_found)?;
+
+    let rule = match (&todo.recurrence, todo.completed) {
+        (Some(rule), false) => rule.clone(),
+        (Some(_), true) => {
+            return Err(ApiError::conflict(
+                "occurrence_completed",
+                "Completed occurrences cannot be skipped",
+            ));
+        }
+        (None, _) => {
+            return Err(ApiError::conflict("not_recurring", "Todo is not recurring"));
+        }
+    };
+
+    let tz = resolve_timezone(None, user_id, db).await?;
+
+    let anchor = todo.due_at.unwrap_or_else(Utc::now);
+    let next = rule.next_after(anchor, tz).ok_or_else(|| {
+        ApiError::conflict("series_ended", "The series has no further occurrences")
+    })?;
+
+    todo.start_at = todo.start_at.map(|start_at| start_at + (next - anchor));
+    todo.due_at = Some(next);
+    todo.updated_at = Utc::now();
+
+    collection
+        .update_one(
+            doc! {"_id": todo_id, "user_id": user_id},
+            doc! {"$set": {
+                "due_at": todo.due_at.map(BsonDateTime::from_chrono),
+                "start_at": todo.start_at.map(BsonDateTime::from_chrono),
+                "updated_at": mongodb::bson::to_bson(&todo.updated_at).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to skip occurrence"))?;
+    Ok(Json(TodoResponse::from(todo)))
+}
+
+/// Stops a series: no occurrence of it will generate a follow-up any more.
+#[post("/todos/<id>/end-series")]
+pub async fn end_series(
+    id: String,
+    user: Scoped<WriteTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoResponse>, ApiError> {
+    let collection: Collection<Todo> = db.database.collection("todos");
+
+    let todo_id = parse_todo_id(&id)?;
+    let user_id = user.object_id()?;
+
+    let todo = collection
+        .find_one(doc! {"_id": todo_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch todo"))?
+        .ok_or_else(todo_not_found)?;
+    let series_id = todo
+        .series_id
+        .ok_or_else(|| ApiError::conflict("not_in_series", "Todo is not part of a series"))?;
+
+    collection
+        .update_many(
+            doc! {"user_id": user_id, "series_id": series_id},
+            doc! {"$set": {"recurrence": null}},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to end series"))?;
+
+    collection
+        .find_one(doc! {"_id": todo_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch todo"))?
+        .map(|todo| Json(TodoResponse::from(todo)))
+        .ok_or_else(todo_not_found)
+}
+
+/// Generates the occurrence that follows a completed recurring todo and links
+/// it to the series. Returns the new todo's id, or `None` when nothing was
+/// generated (not recurring, not completed, already generated, or the series
+/// has ended).
+pub async fn spawn_next_occurrence(
+    todo: &Todo,
+    db: &DatabaseConnection,
+) -> Result<Option<ObjectId>, ApiError> {
+    let collection: Collection<Todo> = db.database.collection("todos");
+
+    let rule = match &todo.recurrence {
+        Some(rule) if todo.completed && todo.next_occurrence_id.is_none() => rule,
+        _ => return Ok(None),
+    };
+
+    let tz = resolve_timezone(None, todo.user_id, db).await?;
+    let anchor = todo.due_at.unwrap_or_else(Utc::now);
+    let next_due = match rule.next_after(anchor, tz) {
+        Some(next_due) => next_due,
+        None => return Ok(None),
+    };
+
+    // Claim the follow-up first so concurrent completions generate it only once
+    let next_id = ObjectId::new();
+    let claimed = collection
+        .update_one(
+            doc! {"_id": todo.id, "user_id": todo.user_id, "next_occurrence_id": null},
+            doc! {"$set": {"next_occurrence_id": next_id}},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to schedule next occurrence"))?;
+    if claimed.modified_count == 0 {
+        return Ok(None);
+    }
+
+    let now = Utc::now();
+    let mut next = todo.clone();
+    next.id = Some(next_id);
+    next.completed = false;
+    next.completed_at = None;
+    next.series_id = todo.series_id.or(todo.id);
+    next.next_occurrence_id = None;
+    next.due_at = Some(next_due);
+    next.start_at = todo.start_at.map(|start_at| start_at + (next_due - anchor));
+    for item in &mut next.items {
+        item.done = false;
+    }
+    next.created_at = now;
+    next.updated_at = now;
+
+    collection
+        .insert_one(&next)
+        .await
+        .map_err(|_| ApiError::database("Failed to create next occurrence"))?;
+
+    Ok(Some(next_id))
+}
@@ -0,0 +1,185 @@
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    Collection,
+};
+use rocket::{get, serde::json::Json, State};
+
+use crate::{
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    handlers::todo::build_todo_filter,
+    middleware::{
+        auth::VerifiedUser,
+        scope::{ReadTodos, Scoped},
+    },
+    models::todo::{
+        SearchHighlights, Todo, TodoQuery, TodoResponse, TodoSearchHit, TodoSearchResults,
+        DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+    },
+    utils::search::{highlight, SearchTerms},
+};
+
+#[get("/todos/search?<q>&<query..>")]
+pub async fn search(
+    q: String,
+    query: TodoQuery,
+    user: Scoped<ReadTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TodoSearchResults>, ApiError> {
+    let user_id = user.object_id()?;
+    let items = search_todos(&q, &query, user_id, db).await?;
+    Ok(Json(TodoSearchResults { items }))
+}
+
+/// Runs a `$text` search over the user's todos, combined with the regular
+/// `get_todos` filters, best matches first.
+pub async fn search_todos(
+    q: &str,
+    query: &TodoQuery,
+    user_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<Vec<TodoSearchHit>, ApiError> {
+    let collection: Collection<Document> = db.database.collection("todos");
+
+    let terms = SearchTerms::parse(q);
+    if terms.is_empty() {
+        return Err(ApiError::bad_request(
+            "invalid_query",
+            "Search query needs at least one term or phrase",
+        ));
+    }
+
+    let mut filter = build_todo_filter(query, user_id, db).await?;
+    filter.insert("$text", doc! {"$search": q});
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let mut cursor = collection
+        .find(filter)
+        .projection(doc! {"score": {"$meta": "textScore"}})
+        .sort(doc! {"score": {"$meta": "textScore"}, "_id": 1})
+        .limit(limit as i64)
+        .await
+        .map_err(|_| ApiError::database("Failed to search todos"))?;
+
+    let mut hits = Vec::new();
+    while cursor
+        .advance()
+        .await
+        .map_err(|_| ApiError::database("Failed to search todos"))?
+    {
+        let mut stored = cursor
+            .deserialize_current()
+            .map_err(|_| ApiError::internal("invalid_data", "Failed to read stored todo"))?;
+        let score = stored.get_f64("score").unwrap_or_default();
+        stored.remove("score");
+        let todo: Todo = mongodb::bson::from_document(stored)
+            .map_err(|_| ApiError::internal("invalid_data", "Failed to read stored todo"))?;
+
+        let highlights = SearchHighlights {
+            title: highlight(&todo.title, &terms),
+            description: todo
+                .description
+                .as_deref()
+                .and_then(|description| highlight(description, &terms)),
+        };
+        hits.push(TodoSearchHit {
+            todo: TodoResponse::from(todo),
+            score,
+            highlights,
+        });
+    }
+
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use chrono::Utc;
+
+    fn todo(user_id: ObjectId, title: &str, description: &str, completed: bool) -> Todo {
+        let now = Utc::now();
+        Todo {
+            id: None,
+            title: title.to_string(),
+            description: Some(description.to_string()),
+            completed,
+            priority: Default::default(),
+            user_id,
+            project_id: None,
+            tag_ids: Vec::new(),
+            tags: Vec::new(),
+            items: Vec::new(),
+            auto_complete: false,
+            recurrence: None,
+            series_id: None,
+            next_occurrence_id: None,
+            due_at: None,
+            start_at: None,
+            completed_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`).
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn searches_only_the_users_todos() {
+        let config = Config {
+            mongodb_uri: std::env::var("MONGODB_TEST_URI")
+                .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
+            database_name: format!("todo_rust_test_{}", ObjectId::new().to_hex()),
+            ..Config::from_env().unwrap()
+        };
+        let db = DatabaseConnection::new(&config).await.unwrap();
+        let todos: Collection<Todo> = db.database.collection("todos");
+
+        let user_id = ObjectId::new();
+        let other_user_id = ObjectId::new();
+        todos
+            .insert_many([
+                todo(user_id, "Buy milk", "Whole milk from the farm shop", false),
+                todo(user_id, "Buy oat milk", "For the coffee", false),
+                todo(user_id, "Call the bank", "Ask about the milk money", true),
+                todo(other_user_id, "Buy milk", "Someone else's milk", false),
+            ])
+            .await
+            .unwrap();
+
+        let query = TodoQuery::default();
+        let hits = search_todos("milk", &query, user_id, &db).await.unwrap();
+        assert_eq!(hits.len(), 3);
+        assert!(hits.iter().all(|hit| hit.todo.user_id == user_id.to_hex()));
+        assert_eq!(
+            hits[0].highlights.title.as_deref(),
+            Some("Buy <mark>milk</mark>")
+        );
+
+        let hits = search_todos("milk -oat", &query, user_id, &db)
+            .await
+            .unwrap();
+        assert!(hits.iter().all(|hit| hit.todo.title != "Buy oat milk"));
+
+        let hits = search_todos("\"farm shop\"", &query, user_id, &db)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+
+        let open_only = TodoQuery {
+            completed: Some(false),
+            ..TodoQuery::default()
+        };
+        let hits = search_todos("milk", &open_only, user_id, &db)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 2);
+
+        db.database.drop().await.unwrap();
+    }
+}
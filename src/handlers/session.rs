@@ -0,0 +1,176 @@
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime, Document},
+    Collection,
+};
+use rocket::{delete, get, serde::json::Json, State};
+
+use crate::{
+    config::Config,
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    middleware::{
+        client::ClientInfo,
+        scope::{ManageAccount, Scoped},
+    },
+    models::refresh_token::RefreshToken,
+    models::session::{Session, SessionResponse},
+};
+
+/// Lists the active sessions, most recently used first.
+#[get("/sessions")]
+pub async fn get_sessions(
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<Vec<SessionResponse>>, ApiError> {
+    let collection: Collection<Session> = db.database.collection("sessions");
+    let user_id = user.object_id()?;
+
+    let mut cursor = collection
+        .find(doc! {
+            "user_id": user_id,
+            "revoked_at": null,
+            "expires_at": {"$gt": BsonDateTime::from_chrono(Utc::now())},
+        })
+        .sort(doc! {"last_seen_at": -1})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch sessions"))?;
+
+    let mut sessions = Vec::new();
+    while cursor.advance().await.unwrap_or(false) {
+        if let Ok(session) = cursor.deserialize_current() {
+            sessions.push(SessionResponse::new(session, user.session_id.as_deref()));
+        }
+    }
+    Ok(Json(sessions))
+}
+
+#[delete("/sessions/<id>")]
+pub async fn revoke_session(
+    id: String,
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let session_id = ObjectId::parse_str(&id)
+        .map_err(|_| ApiError::bad_request("invalid_id", "Invalid session ID"))?;
+    let user_id = user.object_id()?;
+
+    let revoked = revoke_sessions(doc! {"_id": session_id, "user_id": user_id}, db).await?;
+    if revoked == 0 {
+        return Err(ApiError::not_found(
+            "session_not_found",
+            "Session not found",
+        ));
+    }
+    Ok(Json(serde_json::json!({"message": "Session revoked"})))
+}
+
+/// Logs out everywhere, including the session making the request.
+#[delete("/sessions")]
+pub async fn revoke_all_sessions(
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user_id = user.object_id()?;
+
+    let revoked = revoke_sessions(doc! {"user_id": user_id}, db).await?;
+    Ok(Json(serde_json::json!({
+        "message": "Sessions revoked",
+        "revoked": revoked,
+    })))
+}
+
+/// Records a new login and returns its session id.
+pub async fn start_session(
+    user_id: ObjectId,
+    client: &ClientInfo,
+    db: &DatabaseConnection,
+    config: &Config,
+) -> Result<ObjectId, ApiError> {
+    let collection: Collection<Session> = db.database.collection("sessions");
+
+    let now = Utc::now();
+    let result = collection
+        .insert_one(Session {
+            id: None,
+            user_id,
+            device: client.user_agent.clone(),
+            ip: client.ip.clone(),
+            created_at: now,
+            last_seen_at: now,
+            expires_at: now + config.refresh_token_ttl(),
+            revoked_at: None,
+        })
+        .await
+        .map_err(|_| ApiError::database("Failed to create session"))?;
+    Ok(result.inserted_id.as_object_id().unwrap())
+}
+
+/// Keeps a session alive for another refresh token lifetime. Fails when the
+/// session has been revoked in the meantime.
+pub async fn extend_session(
+    session_id: ObjectId,
+    client: &ClientInfo,
+    db: &DatabaseConnection,
+    config: &Config,
+) -> Result<(), ApiError> {
+    let collection: Collection<Session> = db.database.collection("sessions");
+
+    let now = Utc::now();
+    let result = collection
+        .update_one(
+            doc! {"_id": session_id, "revoked_at": null},
+            doc! {"$set": {
+                "last_seen_at": BsonDateTime::from_chrono(now),
+                "expires_at": BsonDateTime::from_chrono(now + config.refresh_token_ttl()),
+                "ip": &client.ip,
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to update session"))?;
+    if result.matched_count == 0 {
+        return Err(ApiError::unauthorized(
+            "session_revoked",
+            "Session has been revoked",
+        ));
+    }
+    Ok(())
+}
+
+/// Revokes the active sessions matching `filter` along with their refresh
+/// tokens, and returns how many sessions were revoked.
+pub async fn revoke_sessions(filter: Document, db: &DatabaseConnection) -> Result<u64, ApiError> {
+    let sessions: Collection<Session> = db.database.collection("sessions");
+    let refresh_tokens: Collection<RefreshToken> = db.database.collection("refresh_tokens");
+
+    let mut filter = filter;
+    filter.insert("revoked_at", mongodb::bson::Bson::Null);
+    let ids: Vec<ObjectId> = sessions
+        .distinct("_id", filter)
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch sessions"))?
+        .into_iter()
+        .filter_map(|id| id.as_object_id())
+        .collect();
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let now = BsonDateTime::from_chrono(Utc::now());
+    sessions
+        .update_many(
+            doc! {"_id": {"$in": &ids}},
+            doc! {"$set": {"revoked_at": now}},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to revoke sessions"))?;
+    refresh_tokens
+        .update_many(
+            doc! {"family_id": {"$in": &ids}, "revoked_at": null},
+            doc! {"$set": {"revoked_at": now}},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to revoke refresh tokens"))?;
+
+    Ok(ids.len() as u64)
+}
@@ -0,0 +1,265 @@
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    options::UpdateOptions,
+    Collection,
+};
+use rocket::{delete, get, post, put, serde::json::Json, State};
+
+use crate::{
+    database::connection::{is_duplicate_key, DatabaseConnection},
+    error::ApiError,
+    middleware::{
+        auth::AuthenticatedUser,
+        scope::{ReadTodos, Scoped, WriteTodos},
+        validated_json::ValidatedJson,
+    },
+    models::tag::{CreateTagRequest, Tag, TagResponse, UpdateTagRequest, DEFAULT_TAG_COLOR},
+    models::todo::Todo,
+};
+
+#[post("/tags", data = "<request>")]
+pub async fn create_tag(
+    request: ValidatedJson<CreateTagRequest>,
+    user: Scoped<WriteTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TagResponse>, ApiError> {
+    let collection: Collection<Tag> = db.database.collection("tags");
+    let user_id = user.object_id()?;
+
+    let name = request.name.trim().to_string();
+    let color = request
+        .color
+        .clone()
+        .unwrap_or_else(|| DEFAULT_TAG_COLOR.to_string());
+
+    let mut tag = Tag {
+        id: None,
+        user_id,
+        name,
+        color,
+        created_at: Utc::now(),
+    };
+
+    // The unique index on `(user_id, name)` rejects duplicate names
+    let result = collection.insert_one(&tag).await.map_err(|error| {
+        if is_duplicate_key(&error) {
+            tag_exists()
+        } else {
+            ApiError::database("Failed to create tag")
+        }
+    })?;
+    tag.id = result.inserted_id.as_object_id();
+    Ok(Json(TagResponse::from(tag)))
+}
+
+#[get("/tags")]
+pub async fn get_tags(
+    user: Scoped<ReadTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<Vec<TagResponse>>, ApiError> {
+    let collection: Collection<Tag> = db.database.collection("tags");
+    let user_id = user.object_id()?;
+
+    let mut cursor = collection
+        .find(doc! {"user_id": user_id})
+        .sort(doc! {"name": 1})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch tags"))?;
+
+    let mut tags = Vec::new();
+    while cursor.advance().await.unwrap_or(false) {
+        if let Ok(tag) = cursor.deserialize_current() {
+            tags.push(TagResponse::from(tag));
+        }
+    }
+    Ok(Json(tags))
+}
+
+#[get("/tags/<id>")]
+pub async fn get_tag(
+    id: String,
+    user: Scoped<ReadTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TagResponse>, ApiError> {
+    let tag = load_tag(&id, &user, db).await?;
+    Ok(Json(TagResponse::from(tag)))
+}
+
+#[put("/tags/<id>", data = "<request>")]
+pub async fn update_tag(
+    id: String,
+    request: ValidatedJson<UpdateTagRequest>,
+    user: Scoped<WriteTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<TagResponse>, ApiError> {
+    let collection: Collection<Tag> = db.database.collection("tags");
+    let todos: Collection<Todo> = db.database.collection("todos");
+
+    let existing = load_tag(&id, &user, db).await?;
+    let (tag_id, user_id) = (existing.id.unwrap(), existing.user_id);
+
+    let mut update_doc = doc! {};
+
+    if let Some(color) = &request.color {
+        update_doc.insert("color", color);
+    }
+
+    let new_name = request
+        .name
+        .as_ref()
+        .map(|name| name.trim().to_string())
+        .filter(|name| *name != existing.name);
+    if let Some(name) = &new_name {
+        update_doc.insert("name", name);
+    }
+
+    if !update_doc.is_empty() {
+        collection
+            .update_one(
+                doc! {"_id": tag_id, "user_id": user_id},
+                doc! {"$set": update_doc},
+            )
+            .await
+            .map_err(|error| {
+                if is_duplicate_key(&error) {
+                    tag_exists()
+                } else {
+                    ApiError::database("Failed to update tag")
+                }
+            })?;
+    }
+
+    // Keep the denormalized tag names on todos in sync
+    if let Some(name) = &new_name {
+        let options = UpdateOptions::builder()
+            .array_filters(vec![doc! {"tag": &existing.name}])
+            .build();
+        todos
+            .update_many(
+                doc! {"user_id": user_id, "tag_ids": tag_id},
+                doc! {"$set": {"tags.$[tag]": name}},
+            )
+            .with_options(options)
+            .await
+            .map_err(|_| ApiError::database("Failed to rename tag on todos"))?;
+    }
+
+    match collection
+        .find_one(doc! {"_id": tag_id, "user_id": user_id})
+        .await
+    {
+        Ok(Some(tag)) => Ok(Json(TagResponse::from(tag))),
+        Ok(None) => Err(ApiError::not_found(
+            "tag_not_found",
+            "Tag not found after update",
+        )),
+        Err(_) => Err(ApiError::database("Failed to fetch updated tag")),
+    }
+}
+
+#[delete("/tags/<id>")]
+pub async fn delete_tag(
+    id: String,
+    user: Scoped<WriteTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<Tag> = db.database.collection("tags");
+    let todos: Collection<Todo> = db.database.collection("todos");
+
+    let tag_id = ObjectId::parse_str(&id)
+        .map_err(|_| ApiError::bad_request("invalid_id", "Invalid tag ID"))?;
+    let user_id = user.object_id()?;
+
+    let tag = collection
+        .find_one_and_delete(doc! {"_id": tag_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to delete tag"))?
+        .ok_or_else(|| ApiError::not_found("tag_not_found", "Tag not found"))?;
+
+    // Detach the tag from every todo that carried it
+    todos
+        .update_many(
+            doc! {"user_id": user_id, "tag_ids": tag_id},
+            doc! {"$pull": {"tag_ids": tag_id, "tags": &tag.name}},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to detach tag from todos"))?;
+
+    Ok(Json(
+        serde_json::json!({"message": "Tag deleted successfully"}),
+    ))
+}
+
+async fn load_tag(
+    id: &str,
+    user: &AuthenticatedUser,
+    db: &DatabaseConnection,
+) -> Result<Tag, ApiError> {
+    let collection: Collection<Tag> = db.database.collection("tags");
+
+    let tag_id = ObjectId::parse_str(id)
+        .map_err(|_| ApiError::bad_request("invalid_id", "Invalid tag ID"))?;
+    let user_id = user.object_id()?;
+
+    collection
+        .find_one(doc! {"_id": tag_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch tag"))?
+        .ok_or_else(|| ApiError::not_found("tag_not_found", "Tag not found"))
+}
+
+fn tag_exists() -> ApiError {
+    ApiError::conflict("tag_exists", "Tag with this name already exists")
+}
+
+/// Resolves tag ids sent by a client into ids and names, checking that every
+/// tag exists and belongs to `user_id`.
+pub async fn resolve_tag_ids(
+    ids: &[String],
+    user_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<(Vec<ObjectId>, Vec<String>), ApiError> {
+    let collection: Collection<Tag> = db.database.collection("tags");
+
+    let mut tag_ids: Vec<ObjectId> = Vec::new();
+    for id in ids {
+        let tag_id = ObjectId::parse_str(id)
+            .map_err(|_| ApiError::validation("tag_ids", format!("Invalid tag ID: {}", id)))?;
+        if !tag_ids.contains(&tag_id) {
+            tag_ids.push(tag_id);
+        }
+    }
+
+    if tag_ids.is_empty() {
+        return Ok((tag_ids, Vec::new()));
+    }
+
+    let mut cursor = collection
+        .find(doc! {"_id": {"$in": &tag_ids}, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch tags"))?;
+
+    let mut found: Vec<Tag> = Vec::new();
+    while cursor.advance().await.unwrap_or(false) {
+        if let Ok(tag) = cursor.deserialize_current() {
+            found.push(tag);
+        }
+    }
+
+    // Keep the order the client sent
+    let mut names = Vec::with_capacity(tag_ids.len());
+    for tag_id in &tag_ids {
+        match found.iter().find(|tag| tag.id.as_ref() == Some(tag_id)) {
+            Some(tag) => names.push(tag.name.clone()),
+            None => {
+                return Err(ApiError::validation(
+                    "tag_ids",
+                    format!("Tag not found: {}", tag_id.to_hex()),
+                ))
+            }
+        }
+    }
+
+    Ok((tag_ids, names))
+}
@@ -1,253 +1,432 @@
 use chrono::Utc;
+use chrono_tz::Tz;
 use mongodb::{
-    bson::{doc, oid::ObjectId},
+    bson::{doc, oid::ObjectId, Bson, DateTime as BsonDateTime, Document},
     Collection,
 };
 use rocket::{delete, get, post, put, serde::json::Json, State};
 
 use crate::{
     database::connection::DatabaseConnection,
-    handlers::auth::ErrorResponse,
-    middleware::auth::AuthenticatedUser,
-    models::todo::{CreateTodoRequest, Todo, TodoResponse, UpdateTodoRequest},
+    error::ApiError,
+    filter::{compile::compile, parser::parse},
+    handlers::{
+        project::resolve_project_id, recurrence::spawn_next_occurrence, tag::resolve_tag_ids,
+    },
+    middleware::{
+        auth::VerifiedUser,
+        scope::{ReadTodos, Scoped, WriteTodos},
+        validated_json::ValidatedJson,
+    },
+    models::recurrence::StoredRecurrenceRule,
+    models::todo::{
+        CreateTodoRequest, DueWindow, TagMode, Todo, TodoPage, TodoQuery, TodoResponse, TodoSort,
+        UpdateTodoRequest, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+    },
+    models::user::User,
+    utils::time::{parse_datetime, parse_timezone, this_week_bounds, today_bounds},
 };
 
 #[post("/todos", data = "<request>")]
 pub async fn create_todo(
-    request: Json<CreateTodoRequest>,
-    user: AuthenticatedUser,
+    request: ValidatedJson<CreateTodoRequest>,
+    user: Scoped<WriteTodos, VerifiedUser>,
     db: &State<DatabaseConnection>,
-) -> Result<Json<TodoResponse>, rocket::response::status::BadRequest<Json<ErrorResponse>>> {
+) -> Result<Json<TodoResponse>, ApiError> {
     let collection: Collection<Todo> = db.database.collection("todos");
+    let user_id = user.object_id()?;
 
-    let user_id = match ObjectId::parse_str(&user.user_id) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Invalid user ID".to_string(),
-            })));
-        }
+    let (tag_ids, tags) = match &request.tag_ids {
+        Some(ids) => resolve_tag_ids(ids, user_id, db).await?,
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let project_id = match &request.project_id {
+        Some(id) => resolve_project_id(id, user_id, db).await?,
+        None => None,
     };
 
+    if let Some(rule) = &request.recurrence {
+        rule.validate()
+            .map_err(|error| ApiError::validation("recurrence", error))?;
+    }
+
+    // A recurring todo starts its own series, identified by the first occurrence's id
+    let todo_id = ObjectId::new();
     let now = Utc::now();
     let todo = Todo {
-        id: None,
-        title: request.title.clone(),
+        id: Some(todo_id),
+        title: request.title.trim().to_string(),
         description: request.description.clone(),
         completed: false,
+        priority: request.priority.unwrap_or_default(),
         user_id,
+        project_id,
+        tag_ids,
+        tags,
+        items: Vec::new(),
+        auto_complete: request.auto_complete.unwrap_or(false),
+        recurrence: request.recurrence.clone(),
+        series_id: request.recurrence.as_ref().map(|_| todo_id),
+        next_occurrence_id: None,
+        due_at: request.due_at,
+        start_at: request.start_at,
+        completed_at: None,
         created_at: now,
         updated_at: now,
     };
 
-    match collection.insert_one(&todo).await {
-        Ok(result) => {
-            let todo_id = result.inserted_id.as_object_id().unwrap().to_hex();
-            let todo_response = TodoResponse {
-                id: todo_id,
-                title: request.title.clone(),
-                description: request.description.clone(),
-                completed: false,
-                user_id: user.user_id,
-                created_at: now,
-                updated_at: now,
-            };
-            Ok(Json(todo_response))
-        }
-        Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Failed to create todo".to_string(),
-        }))),
-    }
+    collection
+        .insert_one(&todo)
+        .await
+        .map_err(|_| ApiError::database("Failed to create todo"))?;
+    Ok(Json(TodoResponse::from(todo)))
 }
 
-#[get("/todos")]
+#[get("/todos?<query..>")]
 pub async fn get_todos(
-    user: AuthenticatedUser,
+    query: TodoQuery,
+    user: Scoped<ReadTodos, VerifiedUser>,
     db: &State<DatabaseConnection>,
-) -> Result<Json<Vec<TodoResponse>>, rocket::response::status::BadRequest<Json<ErrorResponse>>> {
-    let collection: Collection<Todo> = db.database.collection("todos");
-
-    let user_id = match ObjectId::parse_str(&user.user_id) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Invalid user ID".to_string(),
-            })));
-        }
-    };
-
-    match collection.find(doc! {"user_id": user_id}).await {
-        Ok(mut cursor) => {
-            let mut todos = Vec::new();
-            while cursor.advance().await.unwrap_or(false) {
-                if let Ok(todo) = cursor.deserialize_current() {
-                    todos.push(TodoResponse::from(todo));
-                }
-            }
-            Ok(Json(todos))
-        }
-        Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Failed to fetch todos".to_string(),
-        }))),
-    }
+) -> Result<Json<TodoPage>, ApiError> {
+    let user_id = user.object_id()?;
+    Ok(Json(find_todos(&query, user_id, db).await?))
 }
 
 #[get("/todos/<id>")]
 pub async fn get_todo(
     id: String,
-    user: AuthenticatedUser,
+    user: Scoped<ReadTodos, VerifiedUser>,
     db: &State<DatabaseConnection>,
-) -> Result<Json<TodoResponse>, rocket::response::status::BadRequest<Json<ErrorResponse>>> {
+) -> Result<Json<TodoResponse>, ApiError> {
     let collection: Collection<Todo> = db.database.collection("todos");
 
-    let todo_id = match ObjectId::parse_str(&id) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Invalid todo ID".to_string(),
-            })));
-        }
-    };
-
-    let user_id = match ObjectId::parse_str(&user.user_id) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Invalid user ID".to_string(),
-            })));
-        }
-    };
+    let todo_id = parse_todo_id(&id)?;
+    let user_id = user.object_id()?;
 
-    match collection
+    collection
         .find_one(doc! {"_id": todo_id, "user_id": user_id})
         .await
-    {
-        Ok(Some(todo)) => Ok(Json(TodoResponse::from(todo))),
-        Ok(None) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Todo not found".to_string(),
-        }))),
-        Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Failed to fetch todo".to_string(),
-        }))),
-    }
+        .map_err(|_| ApiError::database("Failed to fetch todo"))?
+        .map(|todo| Json(TodoResponse::from(todo)))
+        .ok_or_else(todo_not_found)
 }
 
 #[put("/todos/<id>", data = "<request>")]
 pub async fn update_todo(
     id: String,
-    request: Json<UpdateTodoRequest>,
-    user: AuthenticatedUser,
+    request: ValidatedJson<UpdateTodoRequest>,
+    user: Scoped<WriteTodos, VerifiedUser>,
     db: &State<DatabaseConnection>,
-) -> Result<Json<TodoResponse>, rocket::response::status::BadRequest<Json<ErrorResponse>>> {
+) -> Result<Json<TodoResponse>, ApiError> {
     let collection: Collection<Todo> = db.database.collection("todos");
 
-    let todo_id = match ObjectId::parse_str(&id) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Invalid todo ID".to_string(),
-            })));
-        }
-    };
+    let todo_id = parse_todo_id(&id)?;
+    let user_id = user.object_id()?;
 
-    let user_id = match ObjectId::parse_str(&user.user_id) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Invalid user ID".to_string(),
-            })));
-        }
-    };
-
-    // Build update document
-    let mut update_doc = doc! {"updated_at": mongodb::bson::DateTime::now()};
+    // Build update document; `updated_at` uses the same string form as `Todo`
+    let mut update_doc = doc! {"updated_at": mongodb::bson::to_bson(&Utc::now()).unwrap()};
 
     if let Some(title) = &request.title {
-        update_doc.insert("title", title);
+        update_doc.insert("title", title.trim());
     }
     if let Some(description) = &request.description {
         update_doc.insert("description", description);
     }
     if let Some(completed) = request.completed {
         update_doc.insert("completed", completed);
+        if !completed {
+            update_doc.insert("completed_at", Bson::Null);
+        }
+    }
+    if let Some(priority) = request.priority {
+        update_doc.insert("priority", priority.rank());
+    }
+    if let Some(auto_complete) = request.auto_complete {
+        update_doc.insert("auto_complete", auto_complete);
+    }
+    if let Some(ids) = &request.tag_ids {
+        let (tag_ids, tags) = resolve_tag_ids(ids, user_id, db).await?;
+        update_doc.insert("tag_ids", tag_ids);
+        update_doc.insert("tags", tags);
+    }
+    if let Some(recurrence) = &request.recurrence {
+        if let Some(rule) = recurrence {
+            rule.validate()
+                .map_err(|error| ApiError::validation("recurrence", error))?;
+        }
+        let stored = recurrence.as_ref().map(StoredRecurrenceRule::from);
+        let recurrence = mongodb::bson::to_bson(&stored)
+            .map_err(|_| ApiError::validation("recurrence", "Invalid recurrence rule"))?;
+        update_doc.insert("recurrence", recurrence);
+    }
+    if let Some(due_at) = request.due_at {
+        update_doc.insert("due_at", due_at.map(BsonDateTime::from_chrono));
+    }
+    if let Some(start_at) = request.start_at {
+        update_doc.insert("start_at", start_at.map(BsonDateTime::from_chrono));
+    }
+
+    // A pipeline update, so completing keeps an earlier completion time and
+    // a todo that just became recurring starts a new series in the same
+    // write. `$literal` keeps request values from being read as expressions.
+    let mut set: Document = update_doc
+        .into_iter()
+        .map(|(field, value)| (field, Bson::Document(doc! {"$literal": value})))
+        .collect();
+    if request.completed == Some(true) {
+        let now = BsonDateTime::from_chrono(Utc::now());
+        set.insert("completed_at", doc! {"$ifNull": ["$completed_at", now]});
+    }
+    if matches!(request.recurrence, Some(Some(_))) {
+        set.insert("series_id", doc! {"$ifNull": ["$series_id", todo_id]});
     }
 
-    match collection
+    let result = collection
         .update_one(
             doc! {"_id": todo_id, "user_id": user_id},
-            doc! {"$set": update_doc},
+            vec![doc! {"$set": set}],
         )
         .await
-    {
-        Ok(result) => {
-            if result.matched_count == 0 {
-                return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                    error: "Todo not found".to_string(),
-                })));
-            }
-
-            // Fetch updated todo
-            match collection
-                .find_one(doc! {"_id": todo_id, "user_id": user_id})
-                .await
-            {
-                Ok(Some(todo)) => Ok(Json(TodoResponse::from(todo))),
-                Ok(None) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                    error: "Todo not found after update".to_string(),
-                }))),
-                Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                    error: "Failed to fetch updated todo".to_string(),
-                }))),
-            }
-        }
-        Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Failed to update todo".to_string(),
-        }))),
+        .map_err(|_| ApiError::database("Failed to update todo"))?;
+    if result.matched_count == 0 {
+        return Err(todo_not_found());
+    }
+
+    // Fetch updated todo
+    let mut todo = collection
+        .find_one(doc! {"_id": todo_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch updated todo"))?
+        .ok_or_else(todo_not_found)?;
+
+    // Completing an occurrence of a recurring todo generates the next one
+    if let Some(next_id) = spawn_next_occurrence(&todo, db).await? {
+        todo.next_occurrence_id = Some(next_id);
     }
+    Ok(Json(TodoResponse::from(todo)))
 }
 
 #[delete("/todos/<id>")]
 pub async fn delete_todo(
     id: String,
-    user: AuthenticatedUser,
+    user: Scoped<WriteTodos, VerifiedUser>,
     db: &State<DatabaseConnection>,
-) -> Result<Json<serde_json::Value>, rocket::response::status::BadRequest<Json<ErrorResponse>>> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let collection: Collection<Todo> = db.database.collection("todos");
 
-    let todo_id = match ObjectId::parse_str(&id) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Invalid todo ID".to_string(),
-            })));
-        }
+    let todo_id = parse_todo_id(&id)?;
+    let user_id = user.object_id()?;
+
+    let result = collection
+        .delete_one(doc! {"_id": todo_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to delete todo"))?;
+    if result.deleted_count == 0 {
+        return Err(todo_not_found());
+    }
+    Ok(Json(
+        serde_json::json!({"message": "Todo deleted successfully"}),
+    ))
+}
+
+pub fn parse_todo_id(id: &str) -> Result<ObjectId, ApiError> {
+    ObjectId::parse_str(id).map_err(|_| ApiError::bad_request("invalid_id", "Invalid todo ID"))
+}
+
+pub fn todo_not_found() -> ApiError {
+    ApiError::not_found("todo_not_found", "Todo not found")
+}
+
+/// Runs a todo listing query for `user_id`; shared by `get_todos` and the project routes.
+pub async fn find_todos(
+    query: &TodoQuery,
+    user_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<TodoPage, ApiError> {
+    let collection: Collection<Todo> = db.database.collection("todos");
+
+    let filter = build_todo_filter(query, user_id, db).await?;
+    let sort = query
+        .sort
+        .as_deref()
+        .map(TodoSort::parse)
+        .transpose()
+        .map_err(|error| ApiError::bad_request("invalid_sort", error))?
+        .unwrap_or_default();
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE) as usize;
+
+    let total = match query.include_total {
+        Some(true) => Some(
+            collection
+                .count_documents(filter.clone())
+                .await
+                .map_err(|_| ApiError::database("Failed to count todos"))?,
+        ),
+        _ => None,
     };
 
-    let user_id = match ObjectId::parse_str(&user.user_id) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                error: "Invalid user ID".to_string(),
-            })));
+    let page_filter = match &query.cursor {
+        Some(cursor) => {
+            let after = sort
+                .cursor_filter(cursor)
+                .map_err(|error| ApiError::bad_request("invalid_cursor", error))?;
+            doc! {"$and": [filter, after]}
         }
+        None => filter,
     };
 
-    match collection
-        .delete_one(doc! {"_id": todo_id, "user_id": user_id})
+    // Fetch one extra todo to find out whether there is a next page
+    let mut cursor = collection
+        .find(page_filter)
+        .sort(sort.to_document())
+        .limit(limit as i64 + 1)
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch todos"))?;
+
+    let mut todos: Vec<Todo> = Vec::with_capacity(limit + 1);
+    while cursor
+        .advance()
         .await
+        .map_err(|_| ApiError::database("Failed to fetch todos"))?
     {
-        Ok(result) => {
-            if result.deleted_count == 0 {
-                return Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-                    error: "Todo not found".to_string(),
-                })));
-            }
-            Ok(Json(
-                serde_json::json!({"message": "Todo deleted successfully"}),
-            ))
+        let todo = cursor
+            .deserialize_current()
+            .map_err(|_| ApiError::internal("invalid_data", "Failed to read stored todo"))?;
+        todos.push(todo);
+    }
+
+    let next_cursor = if todos.len() > limit {
+        todos.truncate(limit);
+        let cursor = sort
+            .cursor_after(todos.last().unwrap())
+            .map_err(|error| ApiError::internal("internal_error", error))?;
+        Some(cursor)
+    } else {
+        None
+    };
+
+    Ok(TodoPage {
+        items: todos.into_iter().map(TodoResponse::from).collect(),
+        next_cursor,
+        total,
+    })
+}
+
+/// Builds the `find` filter for `get_todos`, always scoped to `user_id`.
+pub async fn build_todo_filter(
+    query: &TodoQuery,
+    user_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<Document, ApiError> {
+    let mut filter = doc! {"user_id": user_id};
+    let mut conditions: Vec<Document> = Vec::new();
+    let now = Utc::now();
+
+    let needs_timezone = query.due.is_some()
+        || query.due_before.is_some()
+        || query.due_after.is_some()
+        || query.filter.is_some();
+    if needs_timezone {
+        let tz = resolve_timezone(query.tz.as_deref(), user_id, db).await?;
+
+        if let Some(filter) = &query.filter {
+            let expr = parse(filter)?;
+            let compiled = compile(&expr, tz, now)
+                .map_err(|error| ApiError::bad_request("invalid_filter", error))?;
+            conditions.push(compiled);
+        }
+
+        if let Some(due_before) = &query.due_before {
+            let before = parse_datetime(due_before, tz)
+                .map_err(|e| ApiError::bad_request("invalid_date", e.to_string()))?;
+            conditions.push(doc! {"due_at": {"$lt": BsonDateTime::from_chrono(before)}});
+        }
+        if let Some(due_after) = &query.due_after {
+            let after = parse_datetime(due_after, tz)
+                .map_err(|e| ApiError::bad_request("invalid_date", e.to_string()))?;
+            conditions.push(doc! {"due_at": {"$gt": BsonDateTime::from_chrono(after)}});
+        }
+        if let Some(window) = query.due {
+            let bounds = match window {
+                DueWindow::Today => today_bounds(now, tz),
+                DueWindow::ThisWeek => this_week_bounds(now, tz),
+            };
+            let (start, end) =
+                bounds.map_err(|e| ApiError::internal("internal_error", e.to_string()))?;
+            conditions.push(doc! {"due_at": {
+                "$gte": BsonDateTime::from_chrono(start),
+                "$lt": BsonDateTime::from_chrono(end),
+            }});
         }
-        Err(_) => Err(rocket::response::status::BadRequest(Json(ErrorResponse {
-            error: "Failed to delete todo".to_string(),
-        }))),
+    }
+
+    if let Some(completed) = query.completed {
+        conditions.push(doc! {"completed": completed});
+    }
+    if let Some(priority) = query.priority {
+        conditions.push(doc! {"priority": priority.rank()});
+    }
+    if let Some(priority) = query.priority_at_least {
+        conditions.push(doc! {"priority": {"$gte": priority.rank()}});
+    }
+    if let Some(priority) = query.priority_at_most {
+        conditions.push(doc! {"priority": {"$lte": priority.rank()}});
+    }
+
+    if let Some(project) = &query.project {
+        let project_id = resolve_project_id(project, user_id, db).await?;
+        conditions.push(doc! {"project_id": project_id});
+    }
+
+    if !query.tag.is_empty() {
+        let operator = match query.tag_mode.unwrap_or_default() {
+            TagMode::Any => "$in",
+            TagMode::All => "$all",
+        };
+        conditions.push(doc! {"tags": {operator: &query.tag}});
+    }
+
+    match query.overdue {
+        Some(true) => conditions.push(doc! {
+            "completed": false,
+            "due_at": {"$lt": BsonDateTime::from_chrono(now)},
+        }),
+        Some(false) => conditions.push(doc! {"$or": [
+            {"completed": true},
+            {"due_at": null},
+            {"due_at": {"$gte": BsonDateTime::from_chrono(now)}},
+        ]}),
+        None => {}
+    }
+
+    if !conditions.is_empty() {
+        filter.insert("$and", conditions);
+    }
+    Ok(filter)
+}
+
+/// Picks the explicit `tz` parameter, then the user's saved time zone, then UTC.
+pub async fn resolve_timezone(
+    requested: Option<&str>,
+    user_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<Tz, ApiError> {
+    if let Some(name) = requested {
+        return parse_timezone(name)
+            .map_err(|e| ApiError::bad_request("invalid_timezone", e.to_string()));
+    }
+
+    let users: Collection<User> = db.database.collection("users");
+    match users.find_one(doc! {"_id": user_id}).await {
+        Ok(Some(User {
+            timezone: Some(name),
+            ..
+        })) => parse_timezone(&name).map_err(|e| ApiError::internal("invalid_data", e.to_string())),
+        Ok(_) => Ok(Tz::UTC),
+        Err(_) => Err(ApiError::database("Failed to load user settings")),
     }
 }
@@ -0,0 +1,133 @@
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson},
+    Collection,
+};
+use rocket::{post, serde::json::Json, State};
+
+use crate::{
+    config::Config,
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    handlers::one_time_token::{consume_one_time_token, issue_one_time_token},
+    mail::{send_later, Email, SharedMailer},
+    middleware::{
+        scope::{ManageAccount, Scoped},
+        validated_json::ValidatedJson,
+    },
+    models::one_time_token::{OneTimeToken, TokenPurpose, VerifyEmailRequest},
+    models::user::User,
+};
+
+#[post("/verify-email", data = "<request>")]
+pub async fn verify_email(
+    request: ValidatedJson<VerifyEmailRequest>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<User> = db.database.collection("users");
+
+    let token = consume_one_time_token(&request.token, TokenPurpose::EmailVerification, db)
+        .await?
+        .ok_or_else(|| {
+            ApiError::bad_request(
+                "invalid_verification_token",
+                "Invalid, expired or already used verification token",
+            )
+        })?;
+
+    collection
+        .update_one(
+            doc! {"_id": token.user_id},
+            doc! {"$set": {
+                "email_verified": true,
+                "updated_at": to_bson(&Utc::now()).unwrap(),
+            }},
+        )
+        .await
+        .map_err(|_| ApiError::database("Failed to verify email"))?;
+
+    Ok(Json(serde_json::json!({"message": "Email verified"})))
+}
+
+/// Sends a fresh verification link, at most once per
+/// `VERIFICATION_RESEND_SECONDS`.
+#[post("/resend-verification")]
+pub async fn resend_verification(
+    user: Scoped<ManageAccount>,
+    db: &State<DatabaseConnection>,
+    config: &State<Config>,
+    mailer: &State<SharedMailer>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let users: Collection<User> = db.database.collection("users");
+    let tokens: Collection<OneTimeToken> = db.database.collection("one_time_tokens");
+    let user_id = user.object_id()?;
+
+    let account = users
+        .find_one(doc! {"_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .ok_or_else(|| ApiError::unauthorized("invalid_token", "User no longer exists"))?;
+    if account.email_verified {
+        return Err(ApiError::conflict(
+            "already_verified",
+            "Email is already verified",
+        ));
+    }
+
+    let latest = tokens
+        .find_one(doc! {
+            "user_id": user_id,
+            "purpose": to_bson(&TokenPurpose::EmailVerification).unwrap(),
+        })
+        .sort(doc! {"_id": -1})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch verification tokens"))?;
+    if let Some(latest) = latest {
+        let wait =
+            config.verification_resend_seconds - (Utc::now() - latest.created_at).num_seconds();
+        if wait > 0 {
+            return Err(ApiError::too_many_requests(
+                "resend_throttled",
+                format!("Wait {} seconds before requesting another email", wait),
+                wait as u64,
+            ));
+        }
+    }
+
+    send_verification_email(user_id, &account.email, db, config, mailer).await?;
+    Ok(Json(
+        serde_json::json!({"message": "Verification email sent"}),
+    ))
+}
+
+/// Emails `email` a link that verifies it for `user_id`.
+pub async fn send_verification_email(
+    user_id: ObjectId,
+    email: &str,
+    db: &DatabaseConnection,
+    config: &Config,
+    mailer: &SharedMailer,
+) -> Result<(), ApiError> {
+    let token = issue_one_time_token(
+        user_id,
+        TokenPurpose::EmailVerification,
+        None,
+        config.verification_token_ttl(),
+        db,
+    )
+    .await?;
+
+    send_later(
+        mailer,
+        Email {
+            to: email.to_string(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Open this link within {} hours to verify your email address:\n\
+                 {}/verify-email?token={}",
+                config.verification_token_ttl_hours, config.app_url, token
+            ),
+        },
+    );
+    Ok(())
+}
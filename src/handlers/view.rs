@@ -0,0 +1,300 @@
+use chrono::Utc;
+use chrono_tz::Tz;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson},
+    Collection,
+};
+use rocket::{delete, get, post, put, serde::json::Json, State};
+
+use crate::{
+    database::connection::DatabaseConnection,
+    error::ApiError,
+    filter::parser::parse,
+    handlers::{
+        project::resolve_project_id,
+        todo::{find_todos, resolve_timezone},
+    },
+    middleware::{
+        auth::{AuthenticatedUser, VerifiedUser},
+        scope::{ReadTodos, Scoped, WriteTodos},
+        validated_json::ValidatedJson,
+    },
+    models::project::INBOX_ID,
+    models::todo::{TodoQuery, TodoResponse, TodoSort},
+    models::view::{
+        CreateViewRequest, GroupBy, TodoGroup, UpdateViewRequest, View, ViewResponse, ViewTodos,
+    },
+};
+
+#[post("/views", data = "<request>")]
+pub async fn create_view(
+    request: ValidatedJson<CreateViewRequest>,
+    user: Scoped<WriteTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<ViewResponse>, ApiError> {
+    let collection: Collection<View> = db.database.collection("views");
+    let user_id = user.object_id()?;
+
+    let name = request.name.trim().to_string();
+
+    validate_view(
+        request.filter.as_deref(),
+        request.sort.as_deref(),
+        request.project.as_deref(),
+        user_id,
+        db,
+    )
+    .await?;
+
+    let now = Utc::now();
+    let mut view = View {
+        id: None,
+        user_id,
+        name,
+        filter: request.filter.clone(),
+        sort: request.sort.clone(),
+        group_by: request.group_by,
+        project: request.project.clone(),
+        created_at: now,
+        updated_at: now,
+    };
+
+    let result = collection
+        .insert_one(&view)
+        .await
+        .map_err(|_| ApiError::database("Failed to create view"))?;
+    view.id = result.inserted_id.as_object_id();
+    Ok(Json(ViewResponse::from(view)))
+}
+
+#[get("/views")]
+pub async fn get_views(
+    user: Scoped<ReadTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<Vec<ViewResponse>>, ApiError> {
+    let collection: Collection<View> = db.database.collection("views");
+    let user_id = user.object_id()?;
+
+    let mut cursor = collection
+        .find(doc! {"user_id": user_id})
+        .sort(doc! {"created_at": 1, "_id": 1})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch views"))?;
+
+    let mut views = Vec::new();
+    while cursor.advance().await.unwrap_or(false) {
+        if let Ok(view) = cursor.deserialize_current() {
+            views.push(ViewResponse::from(view));
+        }
+    }
+    Ok(Json(views))
+}
+
+#[get("/views/<id>")]
+pub async fn get_view(
+    id: String,
+    user: Scoped<ReadTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<ViewResponse>, ApiError> {
+    let view = load_view(&id, &user, db).await?;
+    Ok(Json(ViewResponse::from(view)))
+}
+
+#[put("/views/<id>", data = "<request>")]
+pub async fn update_view(
+    id: String,
+    request: ValidatedJson<UpdateViewRequest>,
+    user: Scoped<WriteTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<ViewResponse>, ApiError> {
+    let collection: Collection<View> = db.database.collection("views");
+
+    let mut view = load_view(&id, &user, db).await?;
+
+    if let Some(name) = &request.name {
+        view.name = name.trim().to_string();
+    }
+    if let Some(filter) = &request.filter {
+        view.filter = filter.clone();
+    }
+    if let Some(sort) = &request.sort {
+        view.sort = sort.clone();
+    }
+    if let Some(group_by) = request.group_by {
+        view.group_by = group_by;
+    }
+    if let Some(project) = &request.project {
+        view.project = project.clone();
+    }
+    view.updated_at = Utc::now();
+
+    validate_view(
+        view.filter.as_deref(),
+        view.sort.as_deref(),
+        view.project.as_deref(),
+        view.user_id,
+        db,
+    )
+    .await?;
+
+    let result = collection
+        .replace_one(doc! {"_id": view.id, "user_id": view.user_id}, &view)
+        .await
+        .map_err(|_| ApiError::database("Failed to update view"))?;
+    if result.matched_count == 0 {
+        return Err(view_not_found());
+    }
+    Ok(Json(ViewResponse::from(view)))
+}
+
+#[delete("/views/<id>")]
+pub async fn delete_view(
+    id: String,
+    user: Scoped<WriteTodos>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection: Collection<View> = db.database.collection("views");
+
+    let view_id = parse_view_id(&id)?;
+    let user_id = user.object_id()?;
+
+    let result = collection
+        .delete_one(doc! {"_id": view_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to delete view"))?;
+    if result.deleted_count == 0 {
+        return Err(view_not_found());
+    }
+    Ok(Json(
+        serde_json::json!({"message": "View deleted successfully"}),
+    ))
+}
+
+/// Evaluates a view. `filter` in the query string narrows the view's own
+/// filter; `sort` replaces the view's sort.
+#[get("/views/<id>/todos?<query..>")]
+pub async fn get_view_todos(
+    id: String,
+    mut query: TodoQuery,
+    user: Scoped<ReadTodos, VerifiedUser>,
+    db: &State<DatabaseConnection>,
+) -> Result<Json<ViewTodos>, ApiError> {
+    let view = load_view(&id, &user, db).await?;
+
+    if let Some(filter) = &view.filter {
+        query.filter = Some(match &query.filter {
+            Some(extra) => format!("({}) AND ({})", filter, extra),
+            None => filter.clone(),
+        });
+    }
+    if query.sort.is_none() {
+        query.sort = view.sort.clone();
+    }
+    if view.project.is_some() {
+        query.project = view.project.clone();
+    }
+
+    let tz = match view.group_by {
+        Some(GroupBy::Due) => resolve_timezone(query.tz.as_deref(), view.user_id, db).await?,
+        _ => Tz::UTC,
+    };
+
+    let page = find_todos(&query, view.user_id, db).await?;
+    Ok(Json(ViewTodos {
+        groups: group_todos(page.items, view.group_by, tz),
+        view: ViewResponse::from(view),
+        next_cursor: page.next_cursor,
+        total: page.total,
+    }))
+}
+
+fn parse_view_id(id: &str) -> Result<ObjectId, ApiError> {
+    ObjectId::parse_str(id).map_err(|_| ApiError::bad_request("invalid_id", "Invalid view ID"))
+}
+
+fn view_not_found() -> ApiError {
+    ApiError::not_found("view_not_found", "View not found")
+}
+
+async fn load_view(
+    id: &str,
+    user: &AuthenticatedUser,
+    db: &DatabaseConnection,
+) -> Result<View, ApiError> {
+    let collection: Collection<View> = db.database.collection("views");
+
+    let view_id = parse_view_id(id)?;
+    let user_id = user.object_id()?;
+
+    collection
+        .find_one(doc! {"_id": view_id, "user_id": user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch view"))?
+        .ok_or_else(view_not_found)
+}
+
+/// Checks a view's filter and sort up front so that evaluating it cannot fail
+/// on a typo later.
+async fn validate_view(
+    filter: Option<&str>,
+    sort: Option<&str>,
+    project: Option<&str>,
+    user_id: ObjectId,
+    db: &DatabaseConnection,
+) -> Result<(), ApiError> {
+    if let Some(filter) = filter {
+        parse(filter).map_err(|error| ApiError::validation("filter", error.to_string()))?;
+    }
+    if let Some(sort) = sort {
+        TodoSort::parse(sort).map_err(|error| ApiError::validation("sort", error))?;
+    }
+    if let Some(project) = project {
+        resolve_project_id(project, user_id, db).await?;
+    }
+    Ok(())
+}
+
+/// Splits a page of todos into groups, in the order their keys first appear.
+fn group_todos(todos: Vec<TodoResponse>, group_by: Option<GroupBy>, tz: Tz) -> Vec<TodoGroup> {
+    let group_by = match group_by {
+        Some(group_by) => group_by,
+        None => {
+            return vec![TodoGroup {
+                key: None,
+                items: todos,
+            }]
+        }
+    };
+
+    let mut groups: Vec<TodoGroup> = Vec::new();
+    for todo in todos {
+        let keys: Vec<Option<String>> = match group_by {
+            GroupBy::Project => vec![Some(
+                todo.project_id
+                    .clone()
+                    .unwrap_or_else(|| INBOX_ID.to_string()),
+            )],
+            GroupBy::Priority => vec![match mongodb::bson::to_bson(&todo.priority) {
+                Ok(Bson::String(priority)) => Some(priority),
+                _ => None,
+            }],
+            GroupBy::Due => vec![todo
+                .due_at
+                .map(|due_at| due_at.with_timezone(&tz).format("%Y-%m-%d").to_string())],
+            GroupBy::Tag if todo.tags.is_empty() => vec![None],
+            GroupBy::Tag => todo.tags.iter().cloned().map(Some).collect(),
+            GroupBy::Completed => vec![Some(todo.completed.to_string())],
+        };
+
+        for key in keys {
+            match groups.iter_mut().find(|group| group.key == key) {
+                Some(group) => group.items.push(todo.clone()),
+                None => groups.push(TodoGroup {
+                    key,
+                    items: vec![todo.clone()],
+                }),
+            }
+        }
+    }
+    groups
+}
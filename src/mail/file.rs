@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::Utc;
+
+use super::{Email, Mailer};
+
+/// Writes every email to its own file in an outbox directory, for local
+/// development without a mail server.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: &str) -> Self {
+        FileMailer { dir: dir.into() }
+    }
+}
+
+#[rocket::async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let name = format!(
+            "{}-{}.txt",
+            Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+            uuid::Uuid::new_v4()
+        );
+        let contents = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            email.to, email.subject, email.body
+        );
+        tokio::fs::write(self.dir.join(name), contents).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn writes_one_file_per_email() {
+        let dir = std::env::temp_dir().join(format!("outbox-{}", uuid::Uuid::new_v4()));
+        let mailer = FileMailer::new(dir.to_str().unwrap());
+
+        mailer
+            .send(Email {
+                to: "ada@example.com".to_string(),
+                subject: "Hello".to_string(),
+                body: "Body".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let mut entries = std::fs::read_dir(&dir).unwrap();
+        let path = entries.next().unwrap().unwrap().path();
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(contents.starts_with("To: ada@example.com\nSubject: Hello\n"));
+        assert!(entries.next().is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
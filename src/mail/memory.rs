@@ -0,0 +1,43 @@
+use std::sync::Mutex;
+
+use anyhow::Result;
+
+use super::{Email, Mailer};
+
+/// Keeps sent emails in memory, for tests.
+#[derive(Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl MemoryMailer {
+    #[allow(dead_code)] // read by tests
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[rocket::async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, email: Email) -> Result<()> {
+        self.sent.lock().unwrap().push(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_sent_emails() {
+        let mailer = MemoryMailer::default();
+        let email = Email {
+            to: "ada@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "Body".to_string(),
+        };
+        mailer.send(email.clone()).await.unwrap();
+        assert_eq!(mailer.sent(), vec![email]);
+    }
+}
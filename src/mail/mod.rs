@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use crate::config::Config;
+
+pub mod file;
+pub mod memory;
+pub mod smtp;
+
+/// A plain-text email.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers emails. Handlers get the configured implementation as
+/// `&State<SharedMailer>`.
+#[rocket::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<()>;
+}
+
+pub type SharedMailer = Arc<dyn Mailer>;
+
+/// Builds the mailer selected by `MAIL_TRANSPORT`: `smtp`, `file` or `memory`.
+pub fn from_config(config: &Config) -> Result<SharedMailer> {
+    match config.mail_transport.as_str() {
+        "smtp" => {
+            let url = config
+                .smtp_url
+                .as_deref()
+                .ok_or_else(|| anyhow!("SMTP_URL is required when MAIL_TRANSPORT=smtp"))?;
+            Ok(Arc::new(smtp::SmtpMailer::new(url, &config.mail_from)?))
+        }
+        "file" => Ok(Arc::new(file::FileMailer::new(&config.mail_outbox_dir))),
+        "memory" => Ok(Arc::new(memory::MemoryMailer::default())),
+        other => Err(anyhow!("Unknown MAIL_TRANSPORT: {}", other)),
+    }
+}
+
+/// Sends `email` in the background so the response does not wait for (or
+/// reveal anything through) delivery. Failures are logged.
+pub fn send_later(mailer: &SharedMailer, email: Email) {
+    let mailer = Arc::clone(mailer);
+    tokio::spawn(async move {
+        if let Err(error) = mailer.send(email).await {
+            eprintln!("Failed to send email: {}", error);
+        }
+    });
+}
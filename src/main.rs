@@ -1,14 +1,18 @@
 mod config;
 mod database;
+mod error;
+mod filter;
 mod handlers;
+mod mail;
 mod middleware;
 mod models;
+mod oidc;
 mod utils;
 
 use config::Config;
 use database::connection::DatabaseConnection;
 use rocket::http::Method;
-use rocket::{launch, routes};
+use rocket::{catchers, launch, routes};
 use rocket_cors::{AllowedHeaders, AllowedOrigins, CorsOptions};
 
 #[launch]
@@ -21,10 +25,16 @@ async fn rocket() -> _ {
         .await
         .expect("Failed to connect to database");
 
+    let mailer = mail::from_config(&config).expect("Failed to configure mailer");
+    let passwords =
+        utils::password::from_config(&config).expect("Failed to configure password hashing");
+    let jwt_keys = utils::jwt::JwtKeys::from_config(&config).expect("Failed to load JWT keys");
+    let oidc_providers = oidc::from_config(&config).expect("Failed to configure sign-in providers");
+
     // Configure CORS
     let cors = CorsOptions {
         allowed_origins: AllowedOrigins::all(), // In production, you should specify exact origins
-        allowed_methods: vec![Method::Get, Method::Post, Method::Put, Method::Delete]
+        allowed_methods: vec![Method::Get, Method::Post, Method::Put, Method::Patch, Method::Delete]
             .into_iter()
             .map(From::from)
             .collect(),
@@ -38,19 +48,80 @@ async fn rocket() -> _ {
     rocket::build()
         .manage(config)
         .manage(db)
+        .manage(mailer)
+        .manage(passwords)
+        .manage(jwt_keys)
+        .manage(oidc_providers)
         .attach(cors)
         .mount(
             "/api/auth",
-            routes![handlers::auth::signup, handlers::auth::login],
+            routes![
+                handlers::auth::signup,
+                handlers::auth::login,
+                handlers::auth::refresh,
+                handlers::auth::logout,
+                handlers::mfa::verify_mfa,
+                handlers::oidc::start_oidc_login,
+                handlers::oidc::finish_oidc_login,
+                handlers::password_reset::forgot_password,
+                handlers::password_reset::reset_password,
+                handlers::verification::verify_email,
+                handlers::verification::resend_verification
+            ],
         )
         .mount(
             "/api",
             routes![
                 handlers::todo::create_todo,
                 handlers::todo::get_todos,
+                handlers::search::search,
                 handlers::todo::get_todo,
                 handlers::todo::update_todo,
-                handlers::todo::delete_todo
+                handlers::todo::delete_todo,
+                handlers::recurrence::skip_occurrence,
+                handlers::recurrence::end_series,
+                handlers::checklist::add_item,
+                handlers::checklist::reorder_items,
+                handlers::checklist::update_item,
+                handlers::checklist::toggle_item,
+                handlers::checklist::delete_item,
+                handlers::tag::create_tag,
+                handlers::tag::get_tags,
+                handlers::tag::get_tag,
+                handlers::tag::update_tag,
+                handlers::tag::delete_tag,
+                handlers::project::create_project,
+                handlers::project::get_projects,
+                handlers::project::get_project,
+                handlers::project::update_project,
+                handlers::project::delete_project,
+                handlers::project::get_project_todos,
+                handlers::project::move_todo,
+                handlers::view::create_view,
+                handlers::view::get_views,
+                handlers::view::get_view,
+                handlers::view::update_view,
+                handlers::view::delete_view,
+                handlers::view::get_view_todos,
+                handlers::session::get_sessions,
+                handlers::session::revoke_session,
+                handlers::session::revoke_all_sessions,
+                handlers::account::get_me,
+                handlers::account::update_me,
+                handlers::account::change_password,
+                handlers::account::request_email_change,
+                handlers::account::confirm_email_change,
+                handlers::account::delete_me,
+                handlers::mfa::enroll_mfa,
+                handlers::mfa::confirm_mfa,
+                handlers::mfa::disable_mfa,
+                handlers::mfa::regenerate_recovery_codes,
+                handlers::api_token::get_tokens,
+                handlers::api_token::create_token,
+                handlers::api_token::revoke_token,
+                handlers::lockout::unlock_user
             ],
         )
+        .mount("/", routes![handlers::jwks::jwks])
+        .register("/", catchers![error::default_catcher])
 }
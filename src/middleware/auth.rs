@@ -1,40 +1,573 @@
 use crate::config::Config;
-use crate::utils::jwt::verify_jwt;
-use rocket::http::Status;
+use crate::database::connection::DatabaseConnection;
+use crate::error::{guard_failure, ApiError};
+use crate::models::api_token::{ApiToken, API_TOKEN_PREFIX};
+use crate::models::scope::Scope;
+use crate::models::session::Session;
+use crate::models::user::User;
+use crate::utils::jwt::{verify_jwt, JwtKeys};
+use crate::utils::token::hash_token;
+use chrono::{Duration, Utc};
+use mongodb::bson::{doc, oid::ObjectId, DateTime as BsonDateTime};
+use mongodb::Collection;
 use rocket::request::{FromRequest, Outcome, Request};
 
+/// The caller, identified by a JWT from a login or by a personal access token
+/// (`pat_...`). Only JWTs belong to a session.
 pub struct AuthenticatedUser {
     pub user_id: String,
+    #[allow(dead_code)]
     pub email: String,
+    pub session_id: Option<String>,
+    pub scopes: Vec<Scope>,
+}
+
+impl AuthenticatedUser {
+    /// The user id as stored in `user_id` fields, for scoping queries to this user.
+    pub fn object_id(&self) -> Result<ObjectId, ApiError> {
+        ObjectId::parse_str(&self.user_id)
+            .map_err(|_| ApiError::unauthorized("invalid_token", "Invalid user ID in token"))
+    }
+
+    /// The id of the session the token was issued for; `None` for personal
+    /// access tokens.
+    pub fn session_object_id(&self) -> Result<Option<ObjectId>, ApiError> {
+        self.session_id
+            .as_deref()
+            .map(ObjectId::parse_str)
+            .transpose()
+            .map_err(|_| ApiError::unauthorized("invalid_token", "Invalid session ID in token"))
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+impl AsRef<AuthenticatedUser> for AuthenticatedUser {
+    fn as_ref(&self) -> &AuthenticatedUser {
+        self
+    }
+}
+
+/// Fails the request with `error`, keeping it for the catcher.
+fn reject<T>(request: &Request<'_>, error: ApiError) -> Outcome<T, ApiError> {
+    Outcome::Error(guard_failure(request, error))
 }
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for AuthenticatedUser {
-    type Error = &'static str;
+    type Error = ApiError;
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let config = match request.rocket().state::<Config>() {
-            Some(config) => config,
-            None => return Outcome::Error((Status::InternalServerError, "Config not found")),
+        let keys = match request.rocket().state::<JwtKeys>() {
+            Some(keys) => keys,
+            None => {
+                return reject(
+                    request,
+                    ApiError::internal("internal_error", "JWT keys not found"),
+                )
+            }
         };
 
         let auth_header = match request.headers().get_one("Authorization") {
             Some(header) => header,
-            None => return Outcome::Error((Status::Unauthorized, "Missing Authorization header")),
+            None => {
+                return reject(
+                    request,
+                    ApiError::unauthorized("missing_token", "Missing Authorization header"),
+                )
+            }
         };
 
         if !auth_header.starts_with("Bearer ") {
-            return Outcome::Error((Status::Unauthorized, "Invalid Authorization header format"));
+            return reject(
+                request,
+                ApiError::unauthorized("invalid_token", "Invalid Authorization header format"),
+            );
         }
 
         let token = &auth_header[7..]; // Remove "Bearer " prefix
 
-        match verify_jwt(token, &config.jwt_secret) {
-            Ok(claims) => Outcome::Success(AuthenticatedUser {
-                user_id: claims.sub,
-                email: claims.email,
-            }),
-            Err(_) => Outcome::Error((Status::Unauthorized, "Invalid or expired token")),
+        let result = if token.starts_with(API_TOKEN_PREFIX) {
+            authenticate_api_token(request, token).await
+        } else {
+            authenticate_jwt(request, token, keys).await
+        };
+        match result {
+            Ok(user) => Outcome::Success(user),
+            Err(error) => reject(request, error),
+        }
+    }
+}
+
+async fn authenticate_jwt(
+    request: &Request<'_>,
+    token: &str,
+    keys: &JwtKeys,
+) -> Result<AuthenticatedUser, ApiError> {
+    let claims = verify_jwt(token, keys)
+        .map_err(|_| ApiError::unauthorized("invalid_token", "Invalid or expired token"))?;
+
+    let user = AuthenticatedUser {
+        user_id: claims.sub,
+        email: claims.email,
+        session_id: Some(claims.jti),
+        scopes: claims.scopes,
+    };
+    check_session(request, &user).await?;
+    Ok(user)
+}
+
+/// Looks up a personal access token by its hash and records when it was last
+/// used.
+async fn authenticate_api_token(
+    request: &Request<'_>,
+    token: &str,
+) -> Result<AuthenticatedUser, ApiError> {
+    let db = request
+        .rocket()
+        .state::<DatabaseConnection>()
+        .ok_or_else(|| ApiError::internal("internal_error", "Database not found"))?;
+    let tokens: Collection<ApiToken> = db.database.collection("api_tokens");
+    let users: Collection<User> = db.database.collection("users");
+    let invalid = || ApiError::unauthorized("invalid_token", "Invalid or expired token");
+
+    let now = Utc::now();
+    let api_token = tokens
+        .find_one(doc! {
+            "token_hash": hash_token(token),
+            "$or": [
+                {"expires_at": null},
+                {"expires_at": {"$gt": BsonDateTime::from_chrono(now)}},
+            ],
+        })
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch token"))?
+        .ok_or_else(invalid)?;
+    let user = users
+        .find_one(doc! {"_id": api_token.user_id})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .ok_or_else(invalid)?;
+
+    // Write `last_used_at` at most once a minute per token
+    let stale = BsonDateTime::from_chrono(now - Duration::minutes(1));
+    let _ = tokens
+        .update_one(
+            doc! {
+                "_id": api_token.id,
+                "$or": [{"last_used_at": null}, {"last_used_at": {"$lt": stale}}],
+            },
+            doc! {"$set": {"last_used_at": BsonDateTime::from_chrono(now)}},
+        )
+        .await;
+
+    Ok(AuthenticatedUser {
+        user_id: api_token.user_id.to_hex(),
+        email: user.email,
+        session_id: None,
+        scopes: api_token.scopes,
+    })
+}
+
+/// Rejects tokens whose session was revoked (logout, password change) and
+/// records when the session was last used.
+async fn check_session(request: &Request<'_>, user: &AuthenticatedUser) -> Result<(), ApiError> {
+    let db = request
+        .rocket()
+        .state::<DatabaseConnection>()
+        .ok_or_else(|| ApiError::internal("internal_error", "Database not found"))?;
+    let collection: Collection<Session> = db.database.collection("sessions");
+
+    let session_id = user
+        .session_object_id()?
+        .ok_or_else(|| ApiError::unauthorized("invalid_token", "Token has no session"))?;
+    let session = doc! {
+        "_id": session_id,
+        "user_id": user.object_id()?,
+        "revoked_at": null,
+    };
+
+    let found = collection
+        .count_documents(session.clone())
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch session"))?;
+    if found == 0 {
+        return Err(ApiError::unauthorized(
+            "session_revoked",
+            "Session has been revoked",
+        ));
+    }
+
+    // Write `last_seen_at` at most once a minute per session
+    let now = Utc::now();
+    let mut stale = session;
+    stale.insert(
+        "last_seen_at",
+        doc! {"$lt": BsonDateTime::from_chrono(now - Duration::minutes(1))},
+    );
+    let _ = collection
+        .update_one(
+            stale,
+            doc! {"$set": {"last_seen_at": BsonDateTime::from_chrono(now)}},
+        )
+        .await;
+    Ok(())
+}
+
+/// An `AuthenticatedUser` whose email address is verified. Only checked when
+/// `REQUIRE_VERIFIED_EMAIL` is on; guards the todo routes.
+pub struct VerifiedUser(pub AuthenticatedUser);
+
+impl std::ops::Deref for VerifiedUser {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &AuthenticatedUser {
+        &self.0
+    }
+}
+
+impl AsRef<AuthenticatedUser> for VerifiedUser {
+    fn as_ref(&self) -> &AuthenticatedUser {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for VerifiedUser {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match request.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) => user,
+            Outcome::Error(error) => return Outcome::Error(error),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        let required = request
+            .rocket()
+            .state::<Config>()
+            .is_some_and(|config| config.require_verified_email);
+        if !required {
+            return Outcome::Success(VerifiedUser(user));
+        }
+
+        match email_verified(request, &user).await {
+            Ok(true) => Outcome::Success(VerifiedUser(user)),
+            Ok(false) => reject(
+                request,
+                ApiError::forbidden("email_not_verified", "Verify your email address first"),
+            ),
+            Err(error) => reject(request, error),
+        }
+    }
+}
+
+async fn email_verified(request: &Request<'_>, user: &AuthenticatedUser) -> Result<bool, ApiError> {
+    let db = request
+        .rocket()
+        .state::<DatabaseConnection>()
+        .ok_or_else(|| ApiError::internal("internal_error", "Database not found"))?;
+    let collection: Collection<User> = db.database.collection("users");
+
+    collection
+        .find_one(doc! {"_id": user.object_id()?})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .map(|account| account.email_verified)
+        .ok_or_else(|| ApiError::unauthorized("invalid_token", "User no longer exists"))
+}
+
+/// An `AuthenticatedUser` whose account has `is_admin` set; guards the
+/// `/admin` routes.
+pub struct AdminUser(pub AuthenticatedUser);
+
+impl AsRef<AuthenticatedUser> for AdminUser {
+    fn as_ref(&self) -> &AuthenticatedUser {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match request.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) => user,
+            Outcome::Error(error) => return Outcome::Error(error),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        match is_admin(request, &user).await {
+            Ok(true) => Outcome::Success(AdminUser(user)),
+            Ok(false) => reject(
+                request,
+                ApiError::forbidden("admin_required", "Only admins can do this"),
+            ),
+            Err(error) => reject(request, error),
+        }
+    }
+}
+
+async fn is_admin(request: &Request<'_>, user: &AuthenticatedUser) -> Result<bool, ApiError> {
+    let db = request
+        .rocket()
+        .state::<DatabaseConnection>()
+        .ok_or_else(|| ApiError::internal("internal_error", "Database not found"))?;
+    let collection: Collection<User> = db.database.collection("users");
+
+    collection
+        .find_one(doc! {"_id": user.object_id()?})
+        .await
+        .map_err(|_| ApiError::database("Failed to fetch user"))?
+        .map(|account| account.is_admin)
+        .ok_or_else(|| ApiError::unauthorized("invalid_token", "User no longer exists"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        get,
+        http::{Header, Status},
+        local::asynchronous::Client,
+        serde::json::Json,
+    };
+
+    use super::*;
+    use crate::error::default_catcher;
+    use crate::handlers::auth::{create_user, issue_tokens};
+    use crate::handlers::session::{revoke_sessions, start_session};
+    use crate::middleware::client::ClientInfo;
+    use crate::middleware::scope::{ReadTodos, Scoped, WriteTodos};
+    use crate::utils::token::generate_token;
+
+    #[get("/me")]
+    fn me(user: AuthenticatedUser) -> Json<serde_json::Value> {
+        Json(serde_json::json!({"user_id": user.user_id}))
+    }
+
+    #[get("/verified")]
+    fn verified(user: VerifiedUser) -> Json<serde_json::Value> {
+        Json(serde_json::json!({"user_id": user.user_id}))
+    }
+
+    #[get("/todos")]
+    fn read_todos(user: Scoped<ReadTodos>) -> Json<serde_json::Value> {
+        Json(serde_json::json!({"user_id": user.user_id}))
+    }
+
+    #[get("/todos/write")]
+    fn write_todos(user: Scoped<WriteTodos>) -> Json<serde_json::Value> {
+        Json(serde_json::json!({"user_id": user.user_id}))
+    }
+
+    async fn client(config: Config) -> Client {
+        let db = DatabaseConnection::new(&config).await.unwrap();
+        let keys = JwtKeys::from_config(&config).unwrap();
+        let rocket = rocket::build()
+            .manage(config)
+            .manage(db)
+            .manage(keys)
+            .mount("/", rocket::routes![me, verified, read_todos, write_todos])
+            .register("/", rocket::catchers![default_catcher]);
+        Client::untracked(rocket).await.unwrap()
+    }
+
+    fn test_config() -> Config {
+        Config {
+            mongodb_uri: std::env::var("MONGODB_TEST_URI")
+                .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
+            database_name: format!("todo_rust_test_{}", ObjectId::new().to_hex()),
+            jwt_secret: Some("test-secret".to_string()),
+            jwt_algorithm: "HS256".to_string(),
+            ..Config::from_env().unwrap()
         }
     }
+
+    async fn sign_up(client: &Client, email_verified: bool) -> User {
+        let db = client.rocket().state::<DatabaseConnection>().unwrap();
+        let now = Utc::now();
+        let user = User {
+            id: None,
+            email: "ada@example.com".to_string(),
+            password_hash: String::new(),
+            name: "Ada".to_string(),
+            timezone: None,
+            email_verified,
+            mfa: None,
+            is_admin: false,
+            identities: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        };
+        create_user(user, db).await.unwrap()
+    }
+
+    /// Logs `user` in and returns the session id and its access token.
+    async fn log_in(client: &Client, user: &User) -> (ObjectId, String) {
+        let rocket = client.rocket();
+        let (db, config) = (rocket.state().unwrap(), rocket.state().unwrap());
+        let client_info = ClientInfo {
+            ip: None,
+            user_agent: None,
+        };
+        let user_id = user.id.unwrap();
+        let session_id = start_session(user_id, &client_info, db, config)
+            .await
+            .unwrap();
+        let tokens = issue_tokens(
+            user_id,
+            &user.email,
+            session_id,
+            db,
+            config,
+            rocket.state().unwrap(),
+        )
+        .await
+        .unwrap();
+        (session_id, tokens.token)
+    }
+
+    async fn get(client: &Client, path: &str, token: &str) -> (Status, serde_json::Value) {
+        let response = client
+            .get(path.to_string())
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch()
+            .await;
+        let status = response.status();
+        (status, response.into_json().await.unwrap())
+    }
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`).
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn revoking_a_session_rejects_its_access_tokens() {
+        let client = client(test_config()).await;
+        let db = client.rocket().state::<DatabaseConnection>().unwrap();
+        let user = sign_up(&client, true).await;
+        let (session_id, token) = log_in(&client, &user).await;
+        let (_, other_token) = log_in(&client, &user).await;
+
+        let (status, _) = get(&client, "/me", &token).await;
+        assert_eq!(status, Status::Ok);
+
+        revoke_sessions(doc! {"_id": session_id}, db).await.unwrap();
+        let (status, body) = get(&client, "/me", &token).await;
+        assert_eq!(status, Status::Unauthorized);
+        assert_eq!(body["code"], "session_revoked");
+
+        // Other logins keep working
+        let (status, _) = get(&client, "/me", &other_token).await;
+        assert_eq!(status, Status::Ok);
+
+        db.database.drop().await.unwrap();
+    }
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`).
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn unverified_users_are_refused_where_verification_is_required() {
+        let client = client(Config {
+            require_verified_email: true,
+            ..test_config()
+        })
+        .await;
+        let db = client.rocket().state::<DatabaseConnection>().unwrap();
+        let user = sign_up(&client, false).await;
+        let (_, token) = log_in(&client, &user).await;
+
+        let (status, body) = get(&client, "/verified", &token).await;
+        assert_eq!(status, Status::Forbidden);
+        assert_eq!(body["code"], "email_not_verified");
+        // Routes that only need a login stay open, e.g. to resend the email
+        let (status, _) = get(&client, "/me", &token).await;
+        assert_eq!(status, Status::Ok);
+
+        db.database
+            .collection::<User>("users")
+            .update_one(
+                doc! {"_id": user.id},
+                doc! {"$set": {"email_verified": true}},
+            )
+            .await
+            .unwrap();
+        let (status, _) = get(&client, "/verified", &token).await;
+        assert_eq!(status, Status::Ok);
+
+        db.database.drop().await.unwrap();
+    }
+
+    /// Stores a personal access token for `user` the way `create_token` does
+    /// and returns it.
+    async fn create_api_token(client: &Client, user: &User, scopes: Vec<Scope>) -> String {
+        let db = client.rocket().state::<DatabaseConnection>().unwrap();
+        let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+        let api_token = ApiToken {
+            id: None,
+            user_id: user.id.unwrap(),
+            name: "CI".to_string(),
+            token_hash: hash_token(&token),
+            hint: token[..12].to_string(),
+            scopes,
+            expires_at: Some(Utc::now() + Duration::days(1)),
+            last_used_at: None,
+            created_at: Utc::now(),
+        };
+        db.database
+            .collection::<ApiToken>("api_tokens")
+            .insert_one(api_token)
+            .await
+            .unwrap();
+        token
+    }
+
+    /// Run with `cargo test -- --ignored` against a mongod on `MONGODB_TEST_URI`
+    /// (default `mongodb://localhost:27017`).
+    #[rocket::async_test]
+    #[ignore = "needs a local mongod"]
+    async fn personal_access_tokens_are_looked_up_and_scoped() {
+        let client = client(test_config()).await;
+        let db = client.rocket().state::<DatabaseConnection>().unwrap();
+        let tokens = db.database.collection::<ApiToken>("api_tokens");
+        let user = sign_up(&client, true).await;
+        let token = create_api_token(&client, &user, vec![Scope::TodosRead]).await;
+
+        // The prefix routes the token to the `api_tokens` lookup
+        let (status, body) = get(&client, "/todos", &token).await;
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["user_id"], user.id.unwrap().to_hex());
+        let stored = tokens.find_one(doc! {}).await.unwrap().unwrap();
+        assert!(stored.last_used_at.is_some());
+        for unknown in [
+            format!("{}{}", API_TOKEN_PREFIX, generate_token()),
+            generate_token(),
+        ] {
+            let (status, body) = get(&client, "/todos", &unknown).await;
+            assert_eq!(status, Status::Unauthorized);
+            assert_eq!(body["code"], "invalid_token");
+        }
+
+        // Scopes the token was not granted are refused
+        let (status, body) = get(&client, "/todos/write", &token).await;
+        assert_eq!(status, Status::Forbidden);
+        assert_eq!(body["code"], "insufficient_scope");
+
+        // Expired tokens stop working before the TTL index removes them
+        tokens
+            .update_one(
+                doc! {"token_hash": hash_token(&token)},
+                doc! {"$set": {"expires_at": BsonDateTime::from_chrono(Utc::now())}},
+            )
+            .await
+            .unwrap();
+        let (status, body) = get(&client, "/todos", &token).await;
+        assert_eq!(status, Status::Unauthorized);
+        assert_eq!(body["code"], "invalid_token");
+
+        db.database.drop().await.unwrap();
+    }
 }
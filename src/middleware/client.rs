@@ -0,0 +1,23 @@
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// Where a request comes from, recorded on sessions.
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientInfo {
+            ip: request.client_ip().map(|ip| ip.to_string()),
+            // Bounded so a client cannot store arbitrary amounts of text
+            user_agent: request
+                .headers()
+                .get_one("User-Agent")
+                .map(|agent| agent.chars().take(256).collect()),
+        })
+    }
+}
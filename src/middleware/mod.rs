@@ -1 +1,4 @@
 pub mod auth;
+pub mod client;
+pub mod scope;
+pub mod validated_json;
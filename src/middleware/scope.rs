@@ -0,0 +1,79 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use crate::error::{guard_failure, ApiError};
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::scope::Scope;
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// A scope a route requires, named in its `Scoped` guard.
+pub trait RequiredScope {
+    const SCOPE: Scope;
+}
+
+pub struct ReadTodos;
+pub struct WriteTodos;
+pub struct AdminProjects;
+pub struct ManageAccount;
+
+impl RequiredScope for ReadTodos {
+    const SCOPE: Scope = Scope::TodosRead;
+}
+
+impl RequiredScope for WriteTodos {
+    const SCOPE: Scope = Scope::TodosWrite;
+}
+
+impl RequiredScope for AdminProjects {
+    const SCOPE: Scope = Scope::ProjectsAdmin;
+}
+
+impl RequiredScope for ManageAccount {
+    const SCOPE: Scope = Scope::Account;
+}
+
+/// The user guard `U` (`AuthenticatedUser` or `VerifiedUser`) whose token also
+/// carries the scope `S`, e.g. `Scoped<WriteTodos, VerifiedUser>`. Tokens
+/// without it get 403 `insufficient_scope`.
+pub struct Scoped<S, U = AuthenticatedUser> {
+    user: U,
+    scope: PhantomData<fn() -> S>,
+}
+
+impl<S, U: AsRef<AuthenticatedUser>> Deref for Scoped<S, U> {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &AuthenticatedUser {
+        self.user.as_ref()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, S, U> FromRequest<'r> for Scoped<S, U>
+where
+    S: RequiredScope,
+    U: FromRequest<'r, Error = ApiError> + AsRef<AuthenticatedUser> + Send,
+{
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match U::from_request(request).await {
+            Outcome::Success(user) => user,
+            Outcome::Error(error) => return Outcome::Error(error),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        if !user.as_ref().has_scope(S::SCOPE) {
+            let error = ApiError::forbidden(
+                "insufficient_scope",
+                format!("Token lacks the {} scope", S::SCOPE.as_str()),
+            );
+            return Outcome::Error(guard_failure(request, error));
+        }
+
+        Outcome::Success(Scoped {
+            user,
+            scope: PhantomData,
+        })
+    }
+}
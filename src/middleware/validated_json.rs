@@ -0,0 +1,53 @@
+use rocket::data::{self, Data, FromData};
+use rocket::outcome::Outcome;
+use rocket::request::Request;
+use rocket::serde::json::{self, Json};
+use serde::de::DeserializeOwned;
+use serde_json::error::Category;
+use validator::Validate;
+
+use crate::error::{guard_failure, ApiError};
+
+/// A JSON request body that has been deserialized and then checked with its
+/// `Validate` rules. Bodies that fail either step are rejected before the
+/// handler runs: malformed JSON with 400, unknown fields, wrong types and
+/// failed rules with 422.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> std::ops::Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+fn body_error(error: json::Error<'_>) -> ApiError {
+    match error {
+        json::Error::Parse(_, error) if error.classify() == Category::Data => {
+            ApiError::unprocessable("invalid_body", error.to_string())
+        }
+        json::Error::Parse(_, error) => ApiError::bad_request("invalid_json", error.to_string()),
+        json::Error::Io(error) => ApiError::bad_request("invalid_body", error.to_string()),
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned + Validate> FromData<'r> for ValidatedJson<T> {
+    type Error = ApiError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let value = match Json::<T>::from_data(request, data).await {
+            Outcome::Success(Json(value)) => value,
+            Outcome::Forward(forward) => return Outcome::Forward(forward),
+            Outcome::Error((_, error)) => {
+                return Outcome::Error(guard_failure(request, body_error(error)))
+            }
+        };
+
+        match value.validate() {
+            Ok(()) => Outcome::Success(ValidatedJson(value)),
+            Err(errors) => Outcome::Error(guard_failure(request, ApiError::from(errors))),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional;
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+use super::scope::Scope;
+use crate::utils::validation::not_blank;
+
+/// Prefix that tells personal access tokens apart from JWTs.
+pub const API_TOKEN_PREFIX: &str = "pat_";
+
+/// A personal access token for scripts and CI. Only the hash is stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub name: String,
+    pub token_hash: String,
+    // Start of the token, so users can tell their tokens apart
+    pub hint: String,
+    pub scopes: Vec<Scope>,
+    // None never expires; the TTL index drops expired tokens
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct CreateApiTokenRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
+    pub name: String,
+    /// Days until the token expires; omit for a token that never does
+    #[validate(range(min = 1, max = 365, message = "must be between 1 and 365"))]
+    pub expires_in_days: Option<i64>,
+    #[validate(length(min = 1, message = "must name at least one scope"))]
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiTokenResponse {
+    pub id: String,
+    pub name: String,
+    pub hint: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Returned once, on creation; the token cannot be shown again.
+#[derive(Debug, Serialize)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub details: ApiTokenResponse,
+    pub token: String,
+}
+
+impl From<ApiToken> for ApiTokenResponse {
+    fn from(token: ApiToken) -> Self {
+        ApiTokenResponse {
+            id: token.id.unwrap().to_hex(),
+            name: token.name,
+            hint: token.hint,
+            scopes: token.scopes,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            created_at: token.created_at,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+};
+use chrono::{DateTime, Utc};
+
+/// Recent failed logins for one account (`account:<email>`) or one client
+/// (`ip:<address>`). Forgotten a while after the last failure.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoginAttempts {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub key: String,
+    pub failures: u32,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub locked_until: Option<DateTime<Utc>>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
+
+impl LoginAttempts {
+    pub fn account_key(email: &str) -> String {
+        format!("account:{}", email.trim().to_lowercase())
+    }
+
+    pub fn ip_key(ip: &str) -> String {
+        format!("ip:{}", ip)
+    }
+}
+
+/// Seconds to lock out after `failures` failed logins: none for the first
+/// `allowed`, then `base_seconds` doubling with every further failure, up to
+/// `max_seconds`.
+pub fn lockout_seconds(
+    failures: u32,
+    allowed: u32,
+    base_seconds: u64,
+    max_seconds: u64,
+) -> Option<u64> {
+    let excess = failures.checked_sub(allowed).filter(|excess| *excess > 0)?;
+    let factor = 1u64.checked_shl(excess - 1).unwrap_or(u64::MAX);
+    Some(base_seconds.saturating_mul(factor).min(max_seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockouts_double_up_to_the_maximum() {
+        let lockouts: Vec<Option<u64>> = (4..=9)
+            .map(|failures| lockout_seconds(failures, 5, 30, 200))
+            .collect();
+        assert_eq!(lockouts, [None, None, Some(30), Some(60), Some(120), Some(200)]);
+        assert_eq!(lockout_seconds(200, 5, 30, 900), Some(900));
+    }
+}
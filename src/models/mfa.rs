@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+use super::user::AuthResponse;
+
+/// TOTP settings of a user. Stored with `enabled: false` between enrollment
+/// and the first confirmed code.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MfaSettings {
+    // AES-256-GCM encrypted, see `utils::mfa::encrypt_secret`
+    pub secret: String,
+    pub enabled: bool,
+    // Hashes of the unused recovery codes
+    #[serde(default)]
+    pub recovery_codes: Vec<String>,
+    // Last TOTP time step accepted, so a code cannot be replayed
+    #[serde(default)]
+    pub last_used_step: Option<i64>,
+    pub enabled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct EnrollMfaRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ConfirmMfaRequest {
+    #[validate(length(min = 6, max = 6, message = "must be 6 digits"))]
+    pub code: String,
+}
+
+/// Re-authentication for disabling MFA or replacing recovery codes: the
+/// password plus a TOTP or recovery code.
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct MfaReauthRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub password: String,
+    #[validate(length(min = 6, max = 32, message = "must be between 6 and 32 characters"))]
+    pub code: String,
+}
+
+/// Second login step: the challenge from `login` plus a TOTP or recovery code.
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct MfaLoginRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub mfa_token: String,
+    #[validate(length(min = 6, max = 32, message = "must be between 6 and 32 characters"))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaEnrollment {
+    /// Base32, for manual entry
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Shown once; only hashes are kept.
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaChallenge {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    /// Seconds until `mfa_token` expires
+    pub expires_in: i64,
+}
+
+/// `login` either signs the user in or, with MFA enabled, asks for a code.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    MfaRequired(MfaChallenge),
+}
@@ -1,2 +1,14 @@
 pub mod user;
 pub mod todo;
+pub mod tag;
+pub mod project;
+pub mod recurrence;
+pub mod view;
+pub mod refresh_token;
+pub mod session;
+pub mod one_time_token;
+pub mod mfa;
+pub mod api_token;
+pub mod scope;
+pub mod login_attempt;
+pub mod oidc;
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+/// A provider account linked to a user, who can then sign in with it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkedIdentity {
+    pub provider: String,
+    // The provider's `sub` claim, stable across email changes
+    pub subject: String,
+    pub linked_at: DateTime<Utc>,
+}
+
+/// A sign-in started with a provider and not yet finished. Found by the
+/// `state` handed to the provider; only its hash is stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OidcLogin {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub provider: String,
+    pub state_hash: String,
+    // PKCE verifier and nonce, checked when the code is redeemed
+    pub code_verifier: String,
+    pub nonce: String,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OidcAuthorization {
+    /// Send the user here to sign in
+    pub authorization_url: String,
+    /// Comes back with the redirect; compare before finishing the sign-in
+    pub state: String,
+    /// Seconds until the sign-in must be finished
+    pub expires_in: i64,
+}
+
+/// The `code` and `state` the provider redirected back with.
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct OidcCallbackRequest {
+    #[validate(length(min = 1, max = 2048, message = "must be between 1 and 2048 characters"))]
+    pub code: String,
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub state: String,
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+};
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+use crate::utils::validation::password_policy;
+
+/// Failed uses after which a token that allows retries stops working.
+pub const MAX_TOKEN_ATTEMPTS: u32 = 5;
+
+/// What a one-time token may be used for.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+    EmailChange,
+    /// Second login step with MFA
+    MfaChallenge,
+}
+
+/// A single-use, expiring token sent to a user by email. Only the hash is stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OneTimeToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub purpose: TokenPurpose,
+    // New address for `EmailChange`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub token_hash: String,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub used_at: Option<DateTime<Utc>>,
+    // Failed attempts with this token; see `MAX_TOKEN_ATTEMPTS`
+    pub attempts: u32,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ForgotPasswordRequest {
+    #[validate(length(min = 1, max = 254, message = "must be between 1 and 254 characters"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub token: String,
+    #[validate(
+        length(min = 8, max = 128, message = "must be between 8 and 128 characters"),
+        custom(function = "password_policy")
+    )]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct VerifyEmailRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ConfirmEmailChangeRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub token: String,
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use rocket::FromFormField;
+use validator::Validate;
+
+use crate::utils::validation::not_blank;
+
+/// Path id of the built-in Inbox, which holds every todo without a `project_id`.
+pub const INBOX_ID: &str = "inbox";
+pub const INBOX_NAME: &str = "Inbox";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Project {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct CreateProjectRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateProjectRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct MoveTodoRequest {
+    /// Target project id, or `null` / `"inbox"` for the Inbox
+    pub project_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectResponse {
+    pub id: String,
+    pub name: String,
+    pub is_inbox: bool,
+    pub completed_count: u64,
+    pub total_count: u64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl ProjectResponse {
+    pub fn from_project(project: Project, completed_count: u64, total_count: u64) -> Self {
+        ProjectResponse {
+            id: project.id.unwrap().to_hex(),
+            name: project.name,
+            is_inbox: false,
+            completed_count,
+            total_count,
+            created_at: Some(project.created_at),
+            updated_at: Some(project.updated_at),
+        }
+    }
+
+    pub fn inbox(completed_count: u64, total_count: u64) -> Self {
+        ProjectResponse {
+            id: INBOX_ID.to_string(),
+            name: INBOX_NAME.to_string(),
+            is_inbox: true,
+            completed_count,
+            total_count,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+}
+
+/// What happens to a project's todos when the project is deleted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromFormField)]
+pub enum DeleteMode {
+    /// Move the todos to the Inbox
+    #[default]
+    #[field(value = "inbox")]
+    Inbox,
+    /// Delete the todos together with the project
+    #[field(value = "cascade")]
+    Cascade,
+}
@@ -0,0 +1,405 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// RRULE-style weekday codes (`mo`, `tu`, ...).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mo,
+    Tu,
+    We,
+    Th,
+    Fr,
+    Sa,
+    Su,
+}
+
+impl From<Weekday> for chrono::Weekday {
+    fn from(day: Weekday) -> Self {
+        match day {
+            Weekday::Mo => chrono::Weekday::Mon,
+            Weekday::Tu => chrono::Weekday::Tue,
+            Weekday::We => chrono::Weekday::Wed,
+            Weekday::Th => chrono::Weekday::Thu,
+            Weekday::Fr => chrono::Weekday::Fri,
+            Weekday::Sa => chrono::Weekday::Sat,
+            Weekday::Su => chrono::Weekday::Sun,
+        }
+    }
+}
+
+/// A recurrence rule modelled on iCalendar RRULE.
+///
+/// - `daily`: every `interval` days
+/// - `weekly`: on each of `by_weekday`, every `interval` weeks
+/// - `monthly` with `by_month_day`: on that day (`-1` = last day), every `interval` months
+/// - `monthly` with `by_set_pos` and one `by_weekday`: on the nth weekday
+///   (`-1` = last), e.g. the 2nd Tuesday
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub freq: Frequency,
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_weekday: Vec<Weekday>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by_month_day: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by_set_pos: Option<i32>,
+    /// No occurrences are generated after this instant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+fn default_interval() -> u32 {
+    1
+}
+
+/// A `RecurrenceRule` as stored on a todo. `until` is a BSON date there, so it
+/// compares as a date in queries; requests and responses use RFC 3339.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoredRecurrenceRule {
+    freq: Frequency,
+    #[serde(default = "default_interval")]
+    interval: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    by_weekday: Vec<Weekday>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    by_month_day: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    by_set_pos: Option<i32>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "chrono_datetime_as_bson_datetime_optional"
+    )]
+    until: Option<DateTime<Utc>>,
+}
+
+impl From<&RecurrenceRule> for StoredRecurrenceRule {
+    fn from(rule: &RecurrenceRule) -> Self {
+        StoredRecurrenceRule {
+            freq: rule.freq,
+            interval: rule.interval,
+            by_weekday: rule.by_weekday.clone(),
+            by_month_day: rule.by_month_day,
+            by_set_pos: rule.by_set_pos,
+            until: rule.until,
+        }
+    }
+}
+
+impl From<StoredRecurrenceRule> for RecurrenceRule {
+    fn from(rule: StoredRecurrenceRule) -> Self {
+        RecurrenceRule {
+            freq: rule.freq,
+            interval: rule.interval,
+            by_weekday: rule.by_weekday,
+            by_month_day: rule.by_month_day,
+            by_set_pos: rule.by_set_pos,
+            until: rule.until,
+        }
+    }
+}
+
+/// Serde adapter for `Todo::recurrence`, going through `StoredRecurrenceRule`.
+pub mod stored_recurrence {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        rule: &Option<RecurrenceRule>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        rule.as_ref()
+            .map(StoredRecurrenceRule::from)
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<RecurrenceRule>, D::Error> {
+        let rule = Option::<StoredRecurrenceRule>::deserialize(deserializer)?;
+        Ok(rule.map(RecurrenceRule::from))
+    }
+}
+
+impl RecurrenceRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval == 0 {
+            return Err("interval must be at least 1".to_string());
+        }
+
+        match self.freq {
+            Frequency::Daily => {
+                if !self.by_weekday.is_empty()
+                    || self.by_month_day.is_some()
+                    || self.by_set_pos.is_some()
+                {
+                    return Err("Daily rules only accept interval and until".to_string());
+                }
+            }
+            Frequency::Weekly => {
+                if self.by_weekday.is_empty() {
+                    return Err("Weekly rules need at least one by_weekday".to_string());
+                }
+                if self.by_month_day.is_some() || self.by_set_pos.is_some() {
+                    return Err("Weekly rules only accept by_weekday".to_string());
+                }
+            }
+            Frequency::Monthly => match (self.by_month_day, self.by_set_pos) {
+                (Some(day), None) => {
+                    if !self.by_weekday.is_empty() {
+                        return Err("by_month_day cannot be combined with by_weekday".to_string());
+                    }
+                    if day == 0 || !(-1..=31).contains(&day) {
+                        return Err("by_month_day must be 1-31 or -1".to_string());
+                    }
+                }
+                (None, Some(position)) => {
+                    if self.by_weekday.len() != 1 {
+                        return Err("by_set_pos needs exactly one by_weekday".to_string());
+                    }
+                    if position == 0 || !(-1..=5).contains(&position) {
+                        return Err("by_set_pos must be 1-5 or -1".to_string());
+                    }
+                }
+                _ => {
+                    return Err(
+                        "Monthly rules need either by_month_day or by_set_pos with by_weekday"
+                            .to_string(),
+                    );
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Returns the occurrence following `current`, evaluated on the wall clock
+    /// of `tz`, or `None` once the series has passed `until`.
+    pub fn next_after(&self, current: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let local = current.with_timezone(&tz).naive_local();
+        let (date, time) = (local.date(), local.time());
+        let interval = self.interval.max(1);
+
+        let next_date = match self.freq {
+            Frequency::Daily => date + Duration::days(interval as i64),
+            Frequency::Weekly => self.next_weekly(date, interval)?,
+            Frequency::Monthly => {
+                let month = date.with_day(1)? + Months::new(interval);
+                match (self.by_month_day, self.by_set_pos) {
+                    (Some(day), _) => month_day(month, day),
+                    (None, Some(position)) => {
+                        nth_weekday(month, self.by_weekday.first()?.to_owned().into(), position)
+                    }
+                    (None, None) => month_day(month, date.day() as i32),
+                }
+            }
+        };
+
+        let next = tz
+            .from_local_datetime(&next_date.and_time(time))
+            .earliest()
+            .or_else(|| {
+                // The wall-clock time does not exist on that day (DST gap)
+                tz.from_local_datetime(&(next_date.and_time(time) + Duration::hours(1)))
+                    .earliest()
+            })?
+            .with_timezone(&Utc);
+
+        match self.until {
+            Some(until) if next > until => None,
+            _ => Some(next),
+        }
+    }
+
+    fn next_weekly(&self, date: NaiveDate, interval: u32) -> Option<NaiveDate> {
+        let mut days: Vec<u32> = self
+            .by_weekday
+            .iter()
+            .map(|day| chrono::Weekday::from(*day).num_days_from_monday())
+            .collect();
+        days.sort_unstable();
+        days.dedup();
+
+        let today = date.weekday().num_days_from_monday();
+        let monday = date - Duration::days(today as i64);
+
+        // A later day in the same week, otherwise the first day `interval` weeks on
+        match days.iter().find(|day| **day > today) {
+            Some(day) => Some(monday + Duration::days(*day as i64)),
+            None => {
+                let first = *days.first()?;
+                Some(monday + Duration::weeks(interval as i64) + Duration::days(first as i64))
+            }
+        }
+    }
+}
+
+/// Day `day` of the month starting at `first`, clamped to the month's length;
+/// negative values count from the end.
+fn month_day(first: NaiveDate, day: i32) -> NaiveDate {
+    let last = days_in_month(first);
+    let day = if day < 0 {
+        (last as i32 + 1 + day).max(1) as u32
+    } else {
+        (day as u32).min(last)
+    };
+    first.with_day(day).unwrap()
+}
+
+/// The `position`-th `weekday` of the month starting at `first` (`-1` = last).
+/// A 5th weekday that does not exist falls back to the last one.
+fn nth_weekday(first: NaiveDate, weekday: chrono::Weekday, position: i32) -> NaiveDate {
+    let offset = (7 + weekday.num_days_from_monday() as i64
+        - first.weekday().num_days_from_monday() as i64)
+        % 7;
+    let first_match = first + Duration::days(offset);
+    let last_day = first.with_day(days_in_month(first)).unwrap();
+    let last_match = first_match + Duration::weeks((last_day - first_match).num_days() / 7);
+
+    if position < 0 {
+        return last_match;
+    }
+    let candidate = first_match + Duration::weeks(position as i64 - 1);
+    candidate.min(last_match)
+}
+
+fn days_in_month(first: NaiveDate) -> u32 {
+    let next = first + Months::new(1);
+    (next - first).num_days() as u32
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{self, doc, Bson};
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    fn monthly_on_day(day: i32) -> RecurrenceRule {
+        RecurrenceRule {
+            freq: Frequency::Monthly,
+            interval: 1,
+            by_weekday: Vec::new(),
+            by_month_day: Some(day),
+            by_set_pos: None,
+            until: None,
+        }
+    }
+
+    fn monthly_on(position: i32, weekday: Weekday) -> RecurrenceRule {
+        RecurrenceRule {
+            by_month_day: None,
+            by_set_pos: Some(position),
+            by_weekday: vec![weekday],
+            ..monthly_on_day(1)
+        }
+    }
+
+    fn daily() -> RecurrenceRule {
+        RecurrenceRule {
+            freq: Frequency::Daily,
+            by_month_day: None,
+            ..monthly_on_day(1)
+        }
+    }
+
+    #[test]
+    fn month_days_clamp_to_the_end_of_the_month() {
+        let rule = monthly_on_day(31);
+        let feb = rule.next_after(utc("2025-01-31T09:00:00Z"), Tz::UTC).unwrap();
+        assert_eq!(feb, utc("2025-02-28T09:00:00Z"));
+        let mar = rule.next_after(feb, Tz::UTC).unwrap();
+        assert_eq!(mar, utc("2025-03-31T09:00:00Z"));
+
+        let last = monthly_on_day(-1);
+        let leap = last.next_after(utc("2024-01-31T09:00:00Z"), Tz::UTC).unwrap();
+        assert_eq!(leap, utc("2024-02-29T09:00:00Z"));
+    }
+
+    #[test]
+    fn nth_weekdays_fall_back_to_the_last_one() {
+        // 2nd Tuesday: 14 January, then 11 February
+        let second_tuesday = monthly_on(2, Weekday::Tu);
+        let next = second_tuesday.next_after(utc("2025-01-14T09:00:00Z"), Tz::UTC);
+        assert_eq!(next, Some(utc("2025-02-11T09:00:00Z")));
+
+        // February 2025 has four Fridays
+        let fifth_friday = monthly_on(5, Weekday::Fr);
+        let next = fifth_friday.next_after(utc("2025-01-31T09:00:00Z"), Tz::UTC);
+        assert_eq!(next, Some(utc("2025-02-28T09:00:00Z")));
+
+        let last_monday = monthly_on(-1, Weekday::Mo);
+        let next = last_monday.next_after(utc("2025-02-24T09:00:00Z"), Tz::UTC);
+        assert_eq!(next, Some(utc("2025-03-31T09:00:00Z")));
+    }
+
+    #[test]
+    fn weekly_rules_skip_interval_weeks_after_the_last_day() {
+        let rule = RecurrenceRule {
+            freq: Frequency::Weekly,
+            interval: 2,
+            by_weekday: vec![Weekday::Fr, Weekday::Mo, Weekday::We],
+            ..daily()
+        };
+        let friday = rule.next_after(utc("2025-01-15T09:00:00Z"), Tz::UTC).unwrap();
+        assert_eq!(friday, utc("2025-01-17T09:00:00Z"));
+        let monday = rule.next_after(friday, Tz::UTC).unwrap();
+        assert_eq!(monday, utc("2025-01-27T09:00:00Z"));
+    }
+
+    #[test]
+    fn occurrences_keep_the_local_time_across_dst() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        // 09:00 CET, then 09:00 CEST after clocks go forward on 30 March
+        let next = daily().next_after(utc("2025-03-29T08:00:00Z"), berlin);
+        assert_eq!(next, Some(utc("2025-03-30T07:00:00Z")));
+
+        // 02:30 does not exist on 30 March; the occurrence moves to 03:30
+        let next = daily().next_after(utc("2025-03-29T01:30:00Z"), berlin);
+        assert_eq!(next, Some(utc("2025-03-30T01:30:00Z")));
+    }
+
+    #[test]
+    fn series_end_after_until() {
+        let rule = RecurrenceRule {
+            until: Some(utc("2025-01-03T09:00:00Z")),
+            ..daily()
+        };
+        let last = rule.next_after(utc("2025-01-02T09:00:00Z"), Tz::UTC);
+        assert_eq!(last, Some(utc("2025-01-03T09:00:00Z")));
+        assert_eq!(rule.next_after(utc("2025-01-03T09:00:00Z"), Tz::UTC), None);
+    }
+
+    #[test]
+    fn until_is_rfc3339_in_json_and_a_date_when_stored() {
+        let json = r#"{"freq":"daily","until":"2025-06-01T00:00:00Z"}"#;
+        let rule: RecurrenceRule = serde_json::from_str(json).unwrap();
+        assert_eq!(rule.until, Some(utc("2025-06-01T00:00:00Z")));
+        let echoed = serde_json::to_value(&rule).unwrap();
+        assert_eq!(echoed["until"], "2025-06-01T00:00:00Z");
+
+        let stored = bson::to_document(&StoredRecurrenceRule::from(&rule)).unwrap();
+        assert!(matches!(stored.get("until"), Some(Bson::DateTime(_))));
+        let loaded: StoredRecurrenceRule = bson::from_document(stored).unwrap();
+        assert_eq!(RecurrenceRule::from(loaded), rule);
+
+        let legacy = doc! {"freq": "weekly", "by_weekday": ["mo"]};
+        let loaded: StoredRecurrenceRule = bson::from_document(legacy).unwrap();
+        assert_eq!(RecurrenceRule::from(loaded).interval, 1);
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+};
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+/// One refresh token, stored by its hash. Every refresh replaces the token
+/// with a new one in the same family; the family id is the session id.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefreshToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub family_id: ObjectId,
+    pub token_hash: String,
+    // A BSON date so the TTL index can drop expired tokens
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    /// Set once the token has been exchanged; presenting it again is reuse
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub rotated_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct RefreshRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    /// Short-lived access JWT for the `Authorization` header
+    pub token: String,
+    pub refresh_token: String,
+    /// Lifetime of `token` in seconds
+    pub expires_in: i64,
+}
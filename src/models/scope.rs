@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// A permission carried by access tokens and personal access tokens. Logins
+/// get every scope; personal access tokens only the ones chosen for them.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Read todos, checklists, tags, views and projects
+    #[serde(rename = "todos:read")]
+    TodosRead,
+    /// Create, change and delete todos, checklists, tags and views
+    #[serde(rename = "todos:write")]
+    TodosWrite,
+    /// Create, change and delete projects
+    #[serde(rename = "projects:admin")]
+    ProjectsAdmin,
+    /// Profile, password, email, sessions, MFA and personal access tokens
+    #[serde(rename = "account")]
+    Account,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [
+        Scope::TodosRead,
+        Scope::TodosWrite,
+        Scope::ProjectsAdmin,
+        Scope::Account,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::TodosRead => "todos:read",
+            Scope::TodosWrite => "todos:write",
+            Scope::ProjectsAdmin => "projects:admin",
+            Scope::Account => "account",
+        }
+    }
+}
+
+/// Every scope: what logins get, and what access tokens issued before scopes
+/// existed are treated as having.
+pub fn all_scopes() -> Vec<Scope> {
+    Scope::ALL.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_use_their_names() {
+        for scope in Scope::ALL {
+            let json = serde_json::to_value(scope).unwrap();
+            assert_eq!(json, scope.as_str());
+            assert_eq!(serde_json::from_value::<Scope>(json).unwrap(), scope);
+        }
+        assert!(serde_json::from_str::<Scope>("\"todos:delete\"").is_err());
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+};
+use chrono::{DateTime, Utc};
+
+/// One login. Access tokens carry the session id as `jti`, and the session's
+/// refresh tokens use it as their family, so revoking a session ends both.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Session {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    // User-Agent of the client that logged in
+    pub device: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub last_seen_at: DateTime<Utc>,
+    // Pushed forward on every refresh; the TTL index drops the session afterwards
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: String,
+    pub device: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// The session of the token that made this request
+    pub current: bool,
+}
+
+impl SessionResponse {
+    pub fn new(session: Session, current_id: Option<&str>) -> Self {
+        let id = session.id.unwrap().to_hex();
+        SessionResponse {
+            current: current_id == Some(id.as_str()),
+            id,
+            device: session.device,
+            ip: session.ip,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+        }
+    }
+}
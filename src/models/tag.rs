@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+use crate::utils::validation::{not_blank, valid_color};
+
+pub const DEFAULT_TAG_COLOR: &str = "#808080";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub name: String,
+    pub color: String, // "#RRGGBB"
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct CreateTagRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
+    pub name: String,
+    #[validate(custom(function = "valid_color"))]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateTagRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
+    pub name: Option<String>,
+    #[validate(custom(function = "valid_color"))]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagResponse {
+    pub id: String,
+    pub name: String,
+    pub color: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Tag> for TagResponse {
+    fn from(tag: Tag) -> Self {
+        TagResponse {
+            id: tag.id.unwrap().to_hex(),
+            name: tag.name,
+            color: tag.color,
+            created_at: tag.created_at,
+        }
+    }
+}
+
+pub fn is_valid_color(color: &str) -> bool {
+    color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
@@ -1,6 +1,16 @@
-use serde::{Deserialize, Serialize};
-use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional;
 use chrono::{DateTime, Utc};
+use rocket::{FromForm, FromFormField};
+use validator::Validate;
+
+use super::recurrence::{stored_recurrence, RecurrenceRule};
+use crate::utils::cursor::{decode_cursor, encode_cursor};
+use crate::utils::validation::not_blank;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+pub const MAX_PAGE_SIZE: u32 = 200;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Todo {
@@ -9,35 +19,454 @@ pub struct Todo {
     pub title: String,
     pub description: Option<String>,
     pub completed: bool,
+    #[serde(default, with = "priority_as_rank")]
+    pub priority: Priority,
     pub user_id: ObjectId,
+    // `None` means the todo lives in the Inbox
+    #[serde(default)]
+    pub project_id: Option<ObjectId>,
+    #[serde(default)]
+    pub tag_ids: Vec<ObjectId>,
+    // Tag names, denormalized from `tags` so todos can be filtered by name
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Kept sorted by `position`
+    #[serde(default)]
+    pub items: Vec<ChecklistItem>,
+    // Complete the todo automatically once every checklist item is done
+    #[serde(default)]
+    pub auto_complete: bool,
+    #[serde(default, with = "stored_recurrence")]
+    pub recurrence: Option<RecurrenceRule>,
+    // Shared by every occurrence of a recurring todo
+    #[serde(default)]
+    pub series_id: Option<ObjectId>,
+    // Set once the following occurrence has been generated
+    #[serde(default)]
+    pub next_occurrence_id: Option<ObjectId>,
+    // Stored as BSON dates (not strings) so range queries and indexes work
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub start_at: Option<DateTime<Utc>>,
+    // When the todo was last marked completed; cleared when it is reopened
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub completed_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    // Older versions wrote this as a BSON date instead of a string; those are
+    // rewritten at startup, see `DatabaseConnection::normalize_todo_dates`
+    #[serde(deserialize_with = "deserialize_string_or_bson_datetime")]
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
 pub struct CreateTodoRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
     pub title: String,
+    #[validate(length(max = 5000, message = "must be at most 5000 characters"))]
     pub description: Option<String>,
+    pub priority: Option<Priority>,
+    pub project_id: Option<String>,
+    #[validate(length(max = 50, message = "must list at most 50 tags"))]
+    pub tag_ids: Option<Vec<String>>,
+    pub auto_complete: Option<bool>,
+    pub recurrence: Option<RecurrenceRule>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub start_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
 pub struct UpdateTodoRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
     pub title: Option<String>,
+    #[validate(length(max = 5000, message = "must be at most 5000 characters"))]
     pub description: Option<String>,
     pub completed: Option<bool>,
+    pub priority: Option<Priority>,
+    #[validate(length(max = 50, message = "must list at most 50 tags"))]
+    pub tag_ids: Option<Vec<String>>,
+    pub auto_complete: Option<bool>,
+    // `null` clears the value, a missing field leaves it untouched
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub recurrence: Option<Option<RecurrenceRule>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub due_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub start_at: Option<Option<DateTime<Utc>>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TodoResponse {
     pub id: String,
     pub title: String,
     pub description: Option<String>,
     pub completed: bool,
+    pub priority: Priority,
     pub user_id: String,
+    pub project_id: Option<String>,
+    pub tag_ids: Vec<String>,
+    pub tags: Vec<String>,
+    pub items: Vec<ChecklistItemResponse>,
+    pub item_progress: ItemProgress,
+    pub auto_complete: bool,
+    pub recurrence: Option<RecurrenceRule>,
+    pub series_id: Option<String>,
+    pub next_occurrence_id: Option<String>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub start_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChecklistItem {
+    pub id: ObjectId,
+    pub text: String,
+    pub done: bool,
+    pub position: u32,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct CreateChecklistItemRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 500, message = "must be at most 500 characters")
+    )]
+    pub text: String,
+    /// Insert at this position; appended when omitted
+    pub position: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateChecklistItemRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 500, message = "must be at most 500 characters")
+    )]
+    pub text: Option<String>,
+    pub done: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ReorderChecklistRequest {
+    /// Every item id of the todo, in the new order
+    pub item_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecklistItemResponse {
+    pub id: String,
+    pub text: String,
+    pub done: bool,
+    pub position: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl From<ChecklistItem> for ChecklistItemResponse {
+    fn from(item: ChecklistItem) -> Self {
+        ChecklistItemResponse {
+            id: item.id.to_hex(),
+            text: item.text,
+            done: item.done,
+            position: item.position,
+        }
+    }
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, FromFormField,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    None = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    Urgent = 4,
+}
+
+impl Priority {
+    pub fn rank(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_rank(rank: i32) -> Option<Self> {
+        match rank {
+            0 => Some(Priority::None),
+            1 => Some(Priority::Low),
+            2 => Some(Priority::Medium),
+            3 => Some(Priority::High),
+            4 => Some(Priority::Urgent),
+            _ => None,
+        }
+    }
+}
+
+/// Keys accepted by the `sort` parameter of `GET /api/todos`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Priority,
+    DueAt,
+    StartAt,
+    CompletedAt,
+    CreatedAt,
+    UpdatedAt,
+    Title,
+    Completed,
+}
+
+impl SortKey {
+    pub fn parse(key: &str) -> Option<Self> {
+        match key {
+            "priority" => Some(SortKey::Priority),
+            "due_at" => Some(SortKey::DueAt),
+            "start_at" => Some(SortKey::StartAt),
+            "completed_at" => Some(SortKey::CompletedAt),
+            "created_at" => Some(SortKey::CreatedAt),
+            "updated_at" => Some(SortKey::UpdatedAt),
+            "title" => Some(SortKey::Title),
+            "completed" => Some(SortKey::Completed),
+            _ => None,
+        }
+    }
+
+    pub fn field(self) -> &'static str {
+        match self {
+            SortKey::Priority => "priority",
+            SortKey::DueAt => "due_at",
+            SortKey::StartAt => "start_at",
+            SortKey::CompletedAt => "completed_at",
+            SortKey::CreatedAt => "created_at",
+            SortKey::UpdatedAt => "updated_at",
+            SortKey::Title => "title",
+            SortKey::Completed => "completed",
+        }
+    }
+
+    /// Whether `value` can be this key's value in a cursor: the type
+    /// `cursor_after` stores for it, or null for a missing value.
+    fn accepts(self, value: &Bson) -> bool {
+        matches!(
+            (self, value),
+            (_, Bson::Null)
+                | (SortKey::Priority, Bson::Int32(_))
+                | (SortKey::DueAt | SortKey::StartAt | SortKey::CompletedAt, Bson::DateTime(_))
+                | (SortKey::CreatedAt | SortKey::UpdatedAt | SortKey::Title, Bson::String(_))
+                | (SortKey::Completed, Bson::Boolean(_))
+        )
+    }
+}
+
+/// A parsed `sort=priority,-due_at,created_at` specification.
+/// A leading `-` sorts that key in descending order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoSort {
+    pub keys: Vec<(SortKey, bool)>, // (key, descending)
+}
+
+impl TodoSort {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut keys: Vec<(SortKey, bool)> = Vec::new();
+
+        for part in spec.split(',').map(str::trim) {
+            let (name, descending) = match part.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (part, false),
+            };
+            let key = SortKey::parse(name).ok_or_else(|| format!("Invalid sort key: '{}'", part))?;
+            if keys.iter().any(|(existing, _)| *existing == key) {
+                return Err(format!("Duplicate sort key: '{}'", name));
+            }
+            keys.push((key, descending));
+        }
+
+        Ok(TodoSort { keys })
+    }
+
+    /// Converts to a MongoDB sort document, with `_id` as the final tiebreaker.
+    pub fn to_document(&self) -> Document {
+        let mut sort = Document::new();
+        for (key, descending) in &self.keys {
+            sort.insert(key.field(), if *descending { -1 } else { 1 });
+        }
+        sort.insert("_id", 1);
+        sort
+    }
+}
+
+impl TodoSort {
+    /// Builds the opaque `next_cursor` that resumes a listing right after `todo`.
+    pub fn cursor_after(&self, todo: &Todo) -> Result<String, String> {
+        let stored = mongodb::bson::to_document(todo).map_err(|_| "Failed to build cursor")?;
+        let values: Vec<Bson> = self
+            .keys
+            .iter()
+            .map(|(key, _)| stored.get(key.field()).cloned().unwrap_or(Bson::Null))
+            .collect();
+
+        encode_cursor(&doc! {"sort": self.to_document(), "values": values, "id": todo.id})
+            .map_err(|_| "Failed to build cursor".to_string())
+    }
+
+    /// Returns a filter matching the todos that sort strictly after `cursor`.
+    pub fn cursor_filter(&self, cursor: &str) -> Result<Document, String> {
+        let cursor = decode_cursor(cursor).map_err(|e| e.to_string())?;
+        if cursor.get_document("sort").ok() != Some(&self.to_document()) {
+            return Err("Cursor does not match the requested sort".to_string());
+        }
+        let values = match cursor.get_array("values") {
+            Ok(values) if values.len() == self.keys.len() => values,
+            _ => return Err("Invalid cursor".to_string()),
+        };
+        let id = cursor.get_object_id("id").map_err(|_| "Invalid cursor".to_string())?;
+
+        // Values end up in the filter, so anything else (say, an operator
+        // document) is refused
+        if self.keys.iter().zip(values).any(|((key, _), value)| !key.accepts(value)) {
+            return Err("Invalid cursor".to_string());
+        }
+
+        // (k1 > v1) OR (k1 = v1 AND k2 > v2) OR ... OR (all equal AND _id > id)
+        let mut branches: Vec<Document> = Vec::new();
+        let mut equal: Vec<Document> = Vec::new();
+        for ((key, descending), value) in self.keys.iter().zip(values) {
+            let field = key.field();
+            if let Some(after) = after_value(field, *descending, value) {
+                let mut branch = equal.clone();
+                branch.push(after);
+                branches.push(doc! {"$and": branch});
+            }
+            equal.push(doc! {field: value.clone()});
+        }
+        equal.push(doc! {"_id": {"$gt": id}});
+        branches.push(doc! {"$and": equal});
+
+        Ok(doc! {"$or": branches})
+    }
+}
+
+/// Condition for "sorts after `value`" on one key. MongoDB sorts null first and
+/// never matches null with `$gt`/`$lt`, so nulls are handled explicitly.
+fn after_value(field: &str, descending: bool, value: &Bson) -> Option<Document> {
+    match (descending, value) {
+        (false, Bson::Null) => Some(doc! {field: {"$ne": null}}),
+        (false, value) => Some(doc! {field: {"$gt": value.clone()}}),
+        (true, Bson::Null) => None,
+        (true, value) => Some(doc! {"$or": [{field: {"$lt": value.clone()}}, {field: null}]}),
+    }
+}
+
+impl Default for TodoSort {
+    fn default() -> Self {
+        TodoSort {
+            keys: vec![(SortKey::CreatedAt, false)],
+        }
+    }
+}
+
+/// Relative due-date windows, resolved in the user's time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromFormField)]
+pub enum DueWindow {
+    #[field(value = "today")]
+    Today,
+    #[field(value = "this_week")]
+    ThisWeek,
+}
+
+/// How multiple `tag` parameters are combined.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromFormField)]
+pub enum TagMode {
+    #[default]
+    #[field(value = "any")]
+    Any,
+    #[field(value = "all")]
+    All,
+}
+
+/// Query parameters accepted by `GET /api/todos`.
+///
+/// `due_before` and `due_after` take either an RFC 3339 timestamp or a plain
+/// `YYYY-MM-DD` date, which is interpreted as midnight in the user's time zone.
+#[derive(Debug, Default, FromForm)]
+pub struct TodoQuery {
+    pub completed: Option<bool>,
+    pub due_before: Option<String>,
+    pub due_after: Option<String>,
+    pub overdue: Option<bool>,
+    pub due: Option<DueWindow>,
+    pub tz: Option<String>,
+    pub priority: Option<Priority>,
+    /// `priority>=high`
+    #[field(name = "priority>")]
+    pub priority_at_least: Option<Priority>,
+    /// `priority<=low`
+    #[field(name = "priority<")]
+    pub priority_at_most: Option<Priority>,
+    /// Project id, or `inbox` for todos without a project
+    pub project: Option<String>,
+    /// Tag names, e.g. `tag=work&tag=urgent`
+    pub tag: Vec<String>,
+    pub tag_mode: Option<TagMode>,
+    /// Filter expression, e.g. `completed = false AND (priority >= high OR tag:urgent)`
+    pub filter: Option<String>,
+    pub sort: Option<String>,
+    /// Page size, capped at `MAX_PAGE_SIZE`
+    pub limit: Option<u32>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+    /// Also count every matching todo (an extra query)
+    pub include_total: Option<bool>,
+}
+
+/// One page of todos; pass `next_cursor` back as `cursor` to get the next page.
+#[derive(Debug, Serialize)]
+pub struct TodoPage {
+    pub items: Vec<TodoResponse>,
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
+
+/// A full-text search match, ranked by `score`.
+#[derive(Debug, Serialize)]
+pub struct TodoSearchHit {
+    #[serde(flatten)]
+    pub todo: TodoResponse,
+    pub score: f64,
+    pub highlights: SearchHighlights,
+}
+
+/// HTML-escaped snippets with the matched terms wrapped in `<mark>`; `None` when
+/// the field has no literal match (e.g. matched only through stemming).
+#[derive(Debug, Serialize)]
+pub struct SearchHighlights {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TodoSearchResults {
+    pub items: Vec<TodoSearchHit>,
+}
+
 impl From<Todo> for TodoResponse {
     fn from(todo: Todo) -> Self {
         TodoResponse {
@@ -45,9 +474,210 @@ impl From<Todo> for TodoResponse {
             title: todo.title,
             description: todo.description,
             completed: todo.completed,
+            priority: todo.priority,
             user_id: todo.user_id.to_hex(),
+            project_id: todo.project_id.map(|id| id.to_hex()),
+            tag_ids: todo.tag_ids.into_iter().map(ObjectId::to_hex).collect(),
+            tags: todo.tags,
+            item_progress: ItemProgress {
+                done: todo.items.iter().filter(|item| item.done).count(),
+                total: todo.items.len(),
+            },
+            items: todo.items.into_iter().map(ChecklistItemResponse::from).collect(),
+            auto_complete: todo.auto_complete,
+            recurrence: todo.recurrence,
+            series_id: todo.series_id.map(|id| id.to_hex()),
+            next_occurrence_id: todo.next_occurrence_id.map(|id| id.to_hex()),
+            due_at: todo.due_at,
+            start_at: todo.start_at,
+            completed_at: todo.completed_at,
             created_at: todo.created_at,
             updated_at: todo.updated_at,
         }
     }
 }
+
+/// Stores `Priority` as its numeric rank so MongoDB can range-filter and sort on it.
+mod priority_as_rank {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(priority: &Priority, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(priority.rank())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Priority, D::Error> {
+        let rank = i32::deserialize(deserializer)?;
+        Priority::from_rank(rank)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid priority rank {}", rank)))
+    }
+}
+
+fn deserialize_string_or_bson_datetime<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Bson::deserialize(deserializer)? {
+        Bson::DateTime(datetime) => Ok(datetime.to_chrono()),
+        Bson::String(value) => DateTime::parse_from_rfc3339(&value)
+            .map(|datetime| datetime.with_timezone(&Utc))
+            .map_err(serde::de::Error::custom),
+        other => Err(serde::de::Error::custom(format!("invalid date: {}", other))),
+    }
+}
+
+pub fn deserialize_nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::DateTime as BsonDateTime;
+
+    fn todo() -> Todo {
+        let now: DateTime<Utc> = "2026-01-15T09:30:00Z".parse().unwrap();
+        Todo {
+            id: Some(ObjectId::new()),
+            title: "Write report".to_string(),
+            description: None,
+            completed: false,
+            priority: Priority::High,
+            user_id: ObjectId::new(),
+            project_id: None,
+            tag_ids: Vec::new(),
+            tags: Vec::new(),
+            items: Vec::new(),
+            auto_complete: false,
+            recurrence: None,
+            series_id: None,
+            next_occurrence_id: None,
+            due_at: Some(now),
+            start_at: None,
+            completed_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn cursor(sort: &TodoSort, values: Vec<Bson>) -> String {
+        encode_cursor(&doc! {"sort": sort.to_document(), "values": values, "id": ObjectId::new()})
+            .unwrap()
+    }
+
+    #[test]
+    fn sort_parses_several_keys_in_order() {
+        let sort = TodoSort::parse("priority, -due_at,title").unwrap();
+        assert_eq!(
+            sort.keys,
+            vec![(SortKey::Priority, false), (SortKey::DueAt, true), (SortKey::Title, false)]
+        );
+        assert_eq!(
+            sort.to_document(),
+            doc! {"priority": 1, "due_at": -1, "title": 1, "_id": 1}
+        );
+    }
+
+    #[test]
+    fn sort_rejects_duplicate_keys() {
+        assert_eq!(
+            TodoSort::parse("title,-title"),
+            Err("Duplicate sort key: 'title'".to_string())
+        );
+        assert!(TodoSort::parse("due_at,priority,due_at").is_err());
+    }
+
+    #[test]
+    fn sort_rejects_unknown_keys() {
+        assert_eq!(
+            TodoSort::parse("priority,-colour"),
+            Err("Invalid sort key: '-colour'".to_string())
+        );
+        assert!(TodoSort::parse("").is_err());
+        assert!(TodoSort::parse("--title").is_err());
+    }
+
+    #[test]
+    fn cursor_resumes_after_the_last_todo() {
+        let sort = TodoSort::parse("-priority,due_at,start_at,title").unwrap();
+        let todo = todo();
+        let id = todo.id.unwrap();
+
+        let filter = sort.cursor_filter(&sort.cursor_after(&todo).unwrap()).unwrap();
+        let due_at = Bson::DateTime(BsonDateTime::from_chrono(todo.due_at.unwrap()));
+        let priority_after = doc! {"$or": [{"priority": {"$lt": 3}}, {"priority": null}]};
+        let equal = vec![
+            doc! {"priority": 3},
+            doc! {"due_at": due_at.clone()},
+            doc! {"start_at": null},
+            doc! {"title": "Write report"},
+            doc! {"_id": {"$gt": id}},
+        ];
+        assert_eq!(
+            filter,
+            doc! {"$or": [
+                {"$and": [priority_after]},
+                {"$and": [equal[0].clone(), {"due_at": {"$gt": due_at}}]},
+                {"$and": [equal[0].clone(), equal[1].clone(), {"start_at": {"$ne": null}}]},
+                {"$and": [
+                    equal[0].clone(),
+                    equal[1].clone(),
+                    equal[2].clone(),
+                    {"title": {"$gt": "Write report"}},
+                ]},
+                {"$and": equal},
+            ]}
+        );
+    }
+
+    #[test]
+    fn cursor_rejects_values_of_the_wrong_type() {
+        let sort = TodoSort::parse("due_at,title").unwrap();
+        let date = Bson::DateTime(BsonDateTime::now());
+
+        let tampered = [
+            vec![Bson::Document(doc! {"$ne": null}), "a".into()],
+            vec![date.clone(), Bson::Array(vec!["a".into()])],
+            vec!["2026-01-15".into(), "a".into()],
+            vec![date.clone(), Bson::Int32(1)],
+        ];
+        for values in tampered {
+            assert!(sort.cursor_filter(&cursor(&sort, values)).is_err());
+        }
+        assert!(sort.cursor_filter(&cursor(&sort, vec![Bson::Null, "a".into()])).is_ok());
+    }
+
+    #[test]
+    fn cursor_must_match_the_sort() {
+        let sort = TodoSort::parse("title").unwrap();
+        let other = TodoSort::parse("-title").unwrap();
+        let cursor = sort.cursor_after(&todo()).unwrap();
+
+        assert!(other.cursor_filter(&cursor).is_err());
+        assert!(sort.cursor_filter("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn legacy_updated_at_pages_as_a_string() {
+        let sort = TodoSort::parse("updated_at").unwrap();
+        let mut stored = mongodb::bson::to_document(&todo()).unwrap();
+        let updated_at: DateTime<Utc> = "2026-01-15T09:30:00.123Z".parse().unwrap();
+        stored.insert("updated_at", BsonDateTime::from_chrono(updated_at));
+
+        let legacy: Todo = mongodb::bson::from_document(stored.clone()).unwrap();
+        assert_eq!(legacy.updated_at, updated_at);
+        let filter = sort.cursor_filter(&sort.cursor_after(&legacy).unwrap()).unwrap();
+        assert_eq!(
+            filter.get_array("$or").unwrap()[0],
+            Bson::Document(doc! {"$and": [{"updated_at": {"$gt": "2026-01-15T09:30:00.123Z"}}]})
+        );
+
+        // The format `normalize_todo_dates` rewrites legacy values into
+        stored.insert("updated_at", "2026-01-15T09:30:00.123000000Z");
+        let migrated: Todo = mongodb::bson::from_document(stored).unwrap();
+        assert_eq!(migrated.updated_at, updated_at);
+    }
+}
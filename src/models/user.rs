@@ -1,6 +1,13 @@
 use serde::{Deserialize, Serialize};
 use mongodb::bson::oid::ObjectId;
 use chrono::{DateTime, Utc};
+use validator::Validate;
+
+use super::mfa::MfaSettings;
+use super::oidc::LinkedIdentity;
+use super::refresh_token::TokenResponse;
+use super::todo::deserialize_nullable;
+use crate::utils::validation::{not_blank, password_policy, valid_timezone};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
@@ -9,20 +16,107 @@ pub struct User {
     pub email: String,
     pub password_hash: String,
     pub name: String,
+    #[serde(default)]
+    pub timezone: Option<String>, // IANA name, e.g. "Europe/Berlin"
+    // Accounts created before verification existed count as verified
+    #[serde(default = "verified_by_default")]
+    pub email_verified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mfa: Option<MfaSettings>,
+    // Set directly in the database; allows the `/admin` routes
+    #[serde(default)]
+    pub is_admin: bool,
+    // Sign-in providers, see `handlers::oidc`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identities: Vec<LinkedIdentity>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+fn verified_by_default() -> bool {
+    true
+}
+
+impl User {
+    /// Enrolled and confirmed; logins need a second factor.
+    pub fn mfa_enabled(&self) -> bool {
+        self.mfa.as_ref().is_some_and(|mfa| mfa.enabled)
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
 pub struct CreateUserRequest {
+    #[validate(
+        email(message = "must be a valid email address"),
+        length(max = 254, message = "must be at most 254 characters")
+    )]
     pub email: String,
+    #[validate(
+        length(min = 8, max = 128, message = "must be between 8 and 128 characters"),
+        custom(function = "password_policy")
+    )]
     pub password: String,
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
     pub name: String,
+    #[validate(custom(function = "valid_timezone"))]
+    pub timezone: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
 pub struct LoginRequest {
+    #[validate(length(min = 1, max = 254, message = "must be between 1 and 254 characters"))]
     pub email: String,
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub current_password: String,
+    #[validate(
+        length(min = 8, max = 128, message = "must be between 8 and 128 characters"),
+        custom(function = "password_policy")
+    )]
+    pub new_password: String,
+}
+
+/// Omitted fields are left unchanged; a `null` timezone clears it.
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateProfileRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    #[validate(custom(function = "valid_timezone"))]
+    pub timezone: Option<Option<String>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ChangeEmailRequest {
+    #[validate(
+        email(message = "must be a valid email address"),
+        length(max = 254, message = "must be at most 254 characters")
+    )]
+    pub new_email: String,
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct DeleteAccountRequest {
+    #[validate(length(min = 1, max = 128, message = "must be between 1 and 128 characters"))]
     pub password: String,
 }
 
@@ -31,21 +125,29 @@ pub struct UserResponse {
     pub id: String,
     pub email: String,
     pub name: String,
+    pub timezone: Option<String>,
+    pub email_verified: bool,
+    pub mfa_enabled: bool,
     pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
-    pub token: String,
+    #[serde(flatten)]
+    pub tokens: TokenResponse,
     pub user: UserResponse,
 }
 
 impl From<User> for UserResponse {
     fn from(user: User) -> Self {
+        let mfa_enabled = user.mfa_enabled();
         UserResponse {
             id: user.id.unwrap().to_hex(),
             email: user.email,
             name: user.name,
+            timezone: user.timezone,
+            email_verified: user.email_verified,
+            mfa_enabled,
             created_at: user.created_at,
         }
     }
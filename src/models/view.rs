@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+use super::todo::{deserialize_nullable, TodoResponse};
+use crate::utils::validation::not_blank;
+
+/// How the todos of a view are grouped when it is evaluated.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Project,
+    Priority,
+    /// Local due date (`YYYY-MM-DD`)
+    Due,
+    /// A todo with several tags appears in each of their groups
+    Tag,
+    Completed,
+}
+
+/// A saved todo listing ("smart list"): a filter expression, sort and grouping,
+/// optionally limited to one project.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct View {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub name: String,
+    pub filter: Option<String>,
+    pub sort: Option<String>,
+    pub group_by: Option<GroupBy>,
+    // Project id or `inbox`, as accepted by the `project` parameter of `get_todos`
+    pub project: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl View {
+    /// The views every new account starts with.
+    pub fn defaults(user_id: ObjectId, now: DateTime<Utc>) -> Vec<View> {
+        let view = |name: &str, filter: &str, sort: &str, group_by: Option<GroupBy>| View {
+            id: None,
+            user_id,
+            name: name.to_string(),
+            filter: Some(filter.to_string()),
+            sort: Some(sort.to_string()),
+            group_by,
+            project: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        vec![
+            // Overdue todos stay in Today until they are done
+            view(
+                "Today",
+                "completed = false AND due < tomorrow",
+                "due_at,-priority",
+                None,
+            ),
+            view(
+                "Upcoming",
+                "completed = false AND due >= tomorrow",
+                "due_at,-priority",
+                Some(GroupBy::Due),
+            ),
+            view(
+                "Completed this week",
+                "completed = true AND completed_at = this_week",
+                "-completed_at",
+                None,
+            ),
+        ]
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct CreateViewRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
+    pub name: String,
+    #[validate(length(max = 1000, message = "must be at most 1000 characters"))]
+    pub filter: Option<String>,
+    #[validate(length(max = 200, message = "must be at most 200 characters"))]
+    pub sort: Option<String>,
+    pub group_by: Option<GroupBy>,
+    pub project: Option<String>,
+}
+
+/// Omitted fields are left unchanged; `null` clears them.
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateViewRequest {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 100, message = "must be at most 100 characters")
+    )]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    #[validate(length(max = 1000, message = "must be at most 1000 characters"))]
+    pub filter: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    #[validate(length(max = 200, message = "must be at most 200 characters"))]
+    pub sort: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub group_by: Option<Option<GroupBy>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub project: Option<Option<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ViewResponse {
+    pub id: String,
+    pub name: String,
+    pub filter: Option<String>,
+    pub sort: Option<String>,
+    pub group_by: Option<GroupBy>,
+    pub project: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<View> for ViewResponse {
+    fn from(view: View) -> Self {
+        ViewResponse {
+            id: view.id.unwrap().to_hex(),
+            name: view.name,
+            filter: view.filter,
+            sort: view.sort,
+            group_by: view.group_by,
+            project: view.project,
+            created_at: view.created_at,
+            updated_at: view.updated_at,
+        }
+    }
+}
+
+/// Todos sharing one group key; `key` is `null` for todos without a value
+/// (no due date, no tags).
+#[derive(Debug, Serialize)]
+pub struct TodoGroup {
+    pub key: Option<String>,
+    pub items: Vec<TodoResponse>,
+}
+
+/// One page of an evaluated view. Without `group_by` there is a single group
+/// with a `null` key.
+#[derive(Debug, Serialize)]
+pub struct ViewTodos {
+    pub view: ViewResponse,
+    pub groups: Vec<TodoGroup>,
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
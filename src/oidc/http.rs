@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+
+/// Discovery documents and key sets are a few KiB.
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The JSON-over-HTTP calls made to identity providers. Only `https` URLs
+/// are allowed unless `allow_http` is set (`OIDC_ALLOW_HTTP`).
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+}
+
+impl HttpClient {
+    pub fn new(allow_http: bool) -> Result<Self> {
+        let client = Client::builder()
+            .https_only(!allow_http)
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent("todo_rust")
+            .build()?;
+        Ok(HttpClient { client })
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        send_json(self.client.get(url)).await
+    }
+
+    pub async fn post_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+    ) -> Result<T> {
+        send_json(self.client.post(url).form(form)).await
+    }
+}
+
+async fn send_json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+    let mut response = request
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await?;
+    let url = response.url().clone();
+    let status = response.status();
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_RESPONSE_BYTES {
+            return Err(anyhow!("{} sent a response that is too large", url));
+        }
+    }
+    if !status.is_success() {
+        let body = String::from_utf8_lossy(&body);
+        return Err(anyhow!("{} returned {}: {:.200}", url, status, body));
+    }
+    Ok(serde_json::from_slice(&body)?)
+}
@@ -0,0 +1,17 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use mongodb::bson::Document;
+
+/// Encodes a pagination cursor as URL-safe base64 of its BSON bytes, so
+/// clients can pass it around without interpreting it.
+pub fn encode_cursor(cursor: &Document) -> Result<String> {
+    let bytes = mongodb::bson::to_vec(cursor)?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<Document> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| anyhow!("Invalid cursor"))?;
+    Document::from_reader(bytes.as_slice()).map_err(|_| anyhow!("Invalid cursor"))
+}
@@ -0,0 +1,124 @@
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::{rngs::OsRng, Rng, RngCore};
+use totp_rs::{Algorithm, TOTP};
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const STEP_SECONDS: u64 = 30;
+const NONCE_LENGTH: usize = 12;
+// Unambiguous characters only: no 0/o, 1/l/i
+const RECOVERY_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// A new random 160-bit TOTP secret, as recommended by RFC 4226.
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; 20];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Standard authenticator-app settings: SHA-1, 6 digits, 30 second steps.
+pub fn totp(secret: Vec<u8>, issuer: &str, account: &str) -> Result<TOTP> {
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        STEP_SECONDS,
+        secret,
+        Some(issuer.replace(':', "")),
+        account.replace(':', ""),
+    )
+    .map_err(|e| anyhow!("Invalid TOTP settings: {}", e))
+}
+
+/// Checks `code` against the time steps around `now` (one step of clock skew
+/// either way) and returns the matching step. Steps up to `last_used_step`
+/// are rejected, so a code works only once.
+pub fn verify_code(totp: &TOTP, code: &str, now: u64, last_used_step: Option<u64>) -> Option<u64> {
+    let current = now / STEP_SECONDS;
+    (current.saturating_sub(1)..=current + 1)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| totp.generate(step * STEP_SECONDS) == code)
+}
+
+/// Encrypts a TOTP secret with AES-256-GCM for storage, as base64 of the
+/// nonce followed by the ciphertext.
+pub fn encrypt_secret(key: &[u8; 32], secret: &[u8]) -> Result<String> {
+    let cipher = Aes256Gcm::new(key.into());
+    let mut nonce = [0u8; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), secret)
+        .map_err(|_| anyhow!("Failed to encrypt secret"))?;
+    Ok(STANDARD.encode([nonce.as_slice(), &ciphertext].concat()))
+}
+
+pub fn decrypt_secret(key: &[u8; 32], encrypted: &str) -> Result<Vec<u8>> {
+    let bytes = STANDARD
+        .decode(encrypted)
+        .map_err(|_| anyhow!("Invalid encrypted secret"))?;
+    if bytes.len() <= NONCE_LENGTH {
+        return Err(anyhow!("Invalid encrypted secret"));
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Failed to decrypt secret"))
+}
+
+/// Fresh one-time recovery codes such as `k7fp-2mxq-9b`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = OsRng;
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| RECOVERY_ALPHABET[rng.gen_range(0..RECOVERY_ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}-{}", &chars[..4], &chars[4..8], &chars[8..])
+        })
+        .collect()
+}
+
+/// Recovery codes are compared without dashes, spaces or case.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_work_once_within_the_skew_window() {
+        let totp = totp(generate_secret(), "Todo", "ada@example.com").unwrap();
+        let now = 1_700_000_000;
+        let code = totp.generate(now);
+
+        let step = verify_code(&totp, &code, now + 20, None).unwrap();
+        assert_eq!(step, now / STEP_SECONDS);
+        assert_eq!(verify_code(&totp, &code, now + 20, Some(step)), None);
+        assert_eq!(verify_code(&totp, &code, now + 300, None), None);
+    }
+
+    #[test]
+    fn secrets_round_trip_through_encryption() {
+        let key = [7u8; 32];
+        let secret = generate_secret();
+        let encrypted = encrypt_secret(&key, &secret).unwrap();
+
+        assert_eq!(decrypt_secret(&key, &encrypted).unwrap(), secret);
+        assert!(decrypt_secret(&[8u8; 32], &encrypted).is_err());
+    }
+
+    #[test]
+    fn recovery_codes_normalize() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(normalize_recovery_code(&codes[0]).len(), 10);
+        assert_eq!(normalize_recovery_code(" K7FP-2mxq 9b"), "k7fp2mxq9b");
+    }
+}
@@ -1,2 +1,8 @@
+pub mod cursor;
 pub mod jwt;
+pub mod mfa;
 pub mod password;
+pub mod search;
+pub mod time;
+pub mod token;
+pub mod validation;
@@ -1,12 +1,203 @@
-use bcrypt::{hash, verify, DEFAULT_COST};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use argon2::password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::rngs::OsRng;
 
-pub fn hash_password(password: &str) -> Result<String> {
-    let hashed = hash(password, DEFAULT_COST)?;
-    Ok(hashed)
+use crate::config::Config;
+
+/// One password hashing scheme. Hashes are self-describing (PHC or modular
+/// crypt strings), so each hasher can tell its own apart.
+pub trait PasswordHasher: Send + Sync {
+    /// Whether `hash` was made by this scheme.
+    fn recognizes(&self, hash: &str) -> bool;
+    fn hash(&self, password: &str) -> Result<String>;
+    fn verify(&self, password: &str, hash: &str) -> Result<bool>;
+    /// Whether `hash` uses weaker settings than this hasher's.
+    fn is_outdated(&self, hash: &str) -> bool;
+}
+
+pub struct Argon2Hasher {
+    params: Params,
+}
+
+impl Argon2Hasher {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| anyhow!("Invalid argon2 parameters: {}", e))?;
+        Ok(Argon2Hasher { params })
+    }
+}
+
+impl PasswordHasher for Argon2Hasher {
+    fn recognizes(&self, hash: &str) -> bool {
+        hash.starts_with("$argon2")
+    }
+
+    fn hash(&self, password: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow!("Failed to hash password: {}", e))?;
+        Ok(hash.to_string())
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> Result<bool> {
+        // The hash carries its own algorithm and parameters
+        let parsed = PasswordHash::new(hash).map_err(|e| anyhow!("Invalid hash: {}", e))?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok())
+    }
+
+    fn is_outdated(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        let Ok(params) = Params::try_from(&parsed) else {
+            return true;
+        };
+        parsed.algorithm != Algorithm::Argon2id.ident()
+            || parsed.version != Some(Version::V0x13.into())
+            || params.m_cost() < self.params.m_cost()
+            || params.t_cost() < self.params.t_cost()
+            || params.p_cost() < self.params.p_cost()
+    }
+}
+
+pub struct BcryptHasher {
+    cost: u32,
+}
+
+impl BcryptHasher {
+    pub fn new(cost: u32) -> Self {
+        BcryptHasher { cost }
+    }
+}
+
+impl PasswordHasher for BcryptHasher {
+    fn recognizes(&self, hash: &str) -> bool {
+        hash.starts_with("$2")
+    }
+
+    fn hash(&self, password: &str) -> Result<String> {
+        Ok(bcrypt::hash(password, self.cost)?)
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> Result<bool> {
+        Ok(bcrypt::verify(password, hash)?)
+    }
+
+    fn is_outdated(&self, hash: &str) -> bool {
+        // $2b$12$...: the cost is the second field
+        hash.split('$')
+            .nth(2)
+            .and_then(|cost| cost.parse::<u32>().ok())
+            .is_none_or(|cost| cost < self.cost)
+    }
 }
 
-pub fn verify_password(password: &str, hash: &str) -> Result<bool> {
-    let is_valid = verify(password, hash)?;
-    Ok(is_valid)
+/// Hashes new passwords with the configured scheme and verifies hashes of
+/// every supported one. Handlers get it as `&State<Passwords>`.
+pub struct Passwords {
+    current: Box<dyn PasswordHasher>,
+    others: Vec<Box<dyn PasswordHasher>>,
+    dummy_hash: String,
+}
+
+impl Passwords {
+    pub fn new(
+        current: Box<dyn PasswordHasher>,
+        others: Vec<Box<dyn PasswordHasher>>,
+    ) -> Result<Self> {
+        let dummy_hash = current.hash("dummy password")?;
+        Ok(Passwords {
+            current,
+            others,
+            dummy_hash,
+        })
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String> {
+        self.current.hash(password)
+    }
+
+    pub fn verify(&self, password: &str, hash: &str) -> Result<bool> {
+        match self.hasher_for(hash) {
+            Some(hasher) => hasher.verify(password, hash),
+            None => Err(anyhow!("Unsupported password hash")),
+        }
+    }
+
+    /// Whether `hash` should be replaced by a hash from the current scheme and
+    /// settings, which the next successful login does.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        !self.current.recognizes(hash) || self.current.is_outdated(hash)
+    }
+
+    /// Runs a verification against a throwaway hash, so that logins for
+    /// unknown emails take as long as wrong passwords.
+    pub fn dummy_verify(&self, password: &str) {
+        let _ = self.current.verify(password, &self.dummy_hash);
+    }
+
+    fn hasher_for(&self, hash: &str) -> Option<&dyn PasswordHasher> {
+        std::iter::once(&self.current)
+            .chain(&self.others)
+            .find(|hasher| hasher.recognizes(hash))
+            .map(|hasher| hasher.as_ref())
+    }
+}
+
+/// Builds the hashers for `PASSWORD_ALGORITHM`: `argon2id` (the default) or
+/// `bcrypt`. Hashes of the other scheme still verify.
+pub fn from_config(config: &Config) -> Result<Passwords> {
+    let argon2 = Box::new(Argon2Hasher::new(
+        config.argon2_memory_kib,
+        config.argon2_iterations,
+        config.argon2_parallelism,
+    )?);
+    let bcrypt = Box::new(BcryptHasher::new(config.bcrypt_cost));
+
+    match config.password_algorithm.as_str() {
+        "argon2id" => Passwords::new(argon2, vec![bcrypt]),
+        "bcrypt" => Passwords::new(bcrypt, vec![argon2]),
+        other => Err(anyhow!("Unknown PASSWORD_ALGORITHM: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap settings; the defaults take a noticeable time per hash
+    fn argon2() -> Box<dyn PasswordHasher> {
+        Box::new(Argon2Hasher::new(1024, 1, 1).unwrap())
+    }
+
+    #[test]
+    fn bcrypt_hashes_verify_and_are_rehashed() {
+        let bcrypt = BcryptHasher::new(4);
+        let old_hash = bcrypt.hash("passw0rd").unwrap();
+        let passwords = Passwords::new(argon2(), vec![Box::new(bcrypt)]).unwrap();
+
+        assert!(passwords.verify("passw0rd", &old_hash).unwrap());
+        assert!(!passwords.verify("wrong", &old_hash).unwrap());
+        assert!(passwords.needs_rehash(&old_hash));
+
+        let new_hash = passwords.hash("passw0rd").unwrap();
+        assert!(new_hash.starts_with("$argon2id$"));
+        assert!(passwords.verify("passw0rd", &new_hash).unwrap());
+        assert!(!passwords.needs_rehash(&new_hash));
+    }
+
+    #[test]
+    fn weaker_settings_are_outdated() {
+        let weak = Argon2Hasher::new(1024, 1, 1).unwrap().hash("passw0rd").unwrap();
+        assert!(Argon2Hasher::new(2048, 1, 1).unwrap().is_outdated(&weak));
+        assert!(!Argon2Hasher::new(1024, 1, 1).unwrap().is_outdated(&weak));
+
+        let cheap = BcryptHasher::new(4).hash("passw0rd").unwrap();
+        assert!(BcryptHasher::new(5).is_outdated(&cheap));
+        assert!(!BcryptHasher::new(4).is_outdated(&cheap));
+    }
 }
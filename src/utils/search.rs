@@ -0,0 +1,212 @@
+/// A `$text` search string split the way MongoDB reads it: bare words,
+/// `"quoted phrases"` and `-negated` words (which never match, so they are not
+/// highlighted).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SearchTerms {
+    pub terms: Vec<String>,
+    pub phrases: Vec<String>,
+    pub negated: Vec<String>,
+}
+
+impl SearchTerms {
+    pub fn parse(query: &str) -> Self {
+        let mut parsed = SearchTerms::default();
+        let mut rest = query;
+
+        while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+            rest = &rest[start..];
+            if let Some(after_quote) = rest.strip_prefix('"') {
+                let end = after_quote.find('"').unwrap_or(after_quote.len());
+                let phrase = after_quote[..end].trim();
+                if !phrase.is_empty() {
+                    parsed.phrases.push(phrase.to_string());
+                }
+                rest = after_quote.get(end + 1..).unwrap_or("");
+                continue;
+            }
+
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..end];
+            match word.strip_prefix('-') {
+                Some(negated) if !negated.is_empty() => parsed.negated.push(negated.to_string()),
+                Some(_) => {}
+                None => parsed.terms.push(word.to_string()),
+            }
+            rest = &rest[end..];
+        }
+
+        parsed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.phrases.is_empty()
+    }
+
+    fn needles(&self) -> Vec<Vec<char>> {
+        self.phrases
+            .iter()
+            .chain(&self.terms)
+            .map(|needle| fold(needle))
+            .filter(|needle| !needle.is_empty())
+            .collect()
+    }
+}
+
+const SNIPPET_CHARS: usize = 160;
+const SNIPPET_LEAD: usize = 40;
+
+/// Returns an HTML-escaped excerpt of `text` around the first match with every
+/// match wrapped in `<mark>`, or `None` if no term occurs in `text`.
+pub fn highlight(text: &str, terms: &SearchTerms) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let folded = fold(text);
+    let needles = terms.needles();
+
+    // Match ranges in char offsets, longest needle wins at each position
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut position = 0;
+    while position < folded.len() {
+        let found = needles
+            .iter()
+            .filter(|needle| folded[position..].starts_with(needle))
+            .map(|needle| needle.len())
+            .max();
+        match found {
+            Some(len) => {
+                ranges.push((position, position + len));
+                position += len;
+            }
+            None => position += 1,
+        }
+    }
+
+    let first = ranges.first()?.0;
+    let start = if chars.len() <= SNIPPET_CHARS {
+        0
+    } else {
+        first.saturating_sub(SNIPPET_LEAD)
+    };
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    let mut cursor = start;
+    for (match_start, match_end) in ranges {
+        if match_start >= end {
+            break;
+        }
+        let match_end = match_end.min(end);
+        push_escaped(&mut snippet, &chars[cursor..match_start]);
+        snippet.push_str("<mark>");
+        push_escaped(&mut snippet, &chars[match_start..match_end]);
+        snippet.push_str("</mark>");
+        cursor = match_end;
+    }
+    push_escaped(&mut snippet, &chars[cursor..end]);
+    if end < chars.len() {
+        snippet.push('…');
+    }
+
+    Some(snippet)
+}
+
+/// Lowercases char by char so offsets stay aligned with the original text.
+fn fold(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+fn push_escaped(out: &mut String, chars: &[char]) {
+    for c in chars {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(*c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_words_phrases_and_negations() {
+        let parsed = SearchTerms::parse(r#"  buy "fresh  milk" -eggs - bread "" "no end"#);
+        assert_eq!(
+            parsed,
+            SearchTerms {
+                terms: strings(&["buy", "bread"]),
+                phrases: strings(&["fresh  milk", "no end"]),
+                negated: strings(&["eggs"]),
+            }
+        );
+    }
+
+    #[test]
+    fn only_negated_terms_search_for_nothing() {
+        assert!(SearchTerms::parse("-eggs -milk").is_empty());
+        assert!(SearchTerms::parse("   ").is_empty());
+        assert!(!SearchTerms::parse("\"oat milk\"").is_empty());
+    }
+
+    #[test]
+    fn highlights_matches_but_not_negated_terms() {
+        let terms = SearchTerms::parse("tea -cake");
+        assert_eq!(
+            highlight("Tea and cake, then more tea", &terms).as_deref(),
+            Some("<mark>Tea</mark> and cake, then more <mark>tea</mark>")
+        );
+        assert_eq!(highlight("Only cake", &terms), None);
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let terms = SearchTerms::parse(r#"green "green tea""#);
+        assert_eq!(
+            highlight("green tea or green juice", &terms).as_deref(),
+            Some("<mark>green tea</mark> or <mark>green</mark> juice")
+        );
+    }
+
+    #[test]
+    fn highlights_non_ascii_text() {
+        let terms = SearchTerms::parse("CAFÉ istanbul");
+        assert_eq!(
+            highlight("Größe: Café in İstanbul", &terms).as_deref(),
+            Some("Größe: <mark>Café</mark> in <mark>İstanbul</mark>")
+        );
+    }
+
+    #[test]
+    fn snippets_cut_long_text_on_char_boundaries() {
+        let text = format!("{}Straße{}", "ü".repeat(100), "ö".repeat(200));
+        let snippet = highlight(&text, &SearchTerms::parse("straße")).unwrap();
+
+        let expected = format!(
+            "…{}<mark>Straße</mark>{}…",
+            "ü".repeat(SNIPPET_LEAD),
+            "ö".repeat(SNIPPET_CHARS - SNIPPET_LEAD - 6)
+        );
+        assert_eq!(snippet, expected);
+    }
+
+    #[test]
+    fn escapes_html_around_matches() {
+        let terms = SearchTerms::parse("tea");
+        assert_eq!(
+            highlight("<b>tea</b> & \"cake\"", &terms).as_deref(),
+            Some("&lt;b&gt;<mark>tea</mark>&lt;/b&gt; &amp; &quot;cake&quot;")
+        );
+    }
+}
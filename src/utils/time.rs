@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    name.parse::<Tz>()
+        .map_err(|_| anyhow!("Unknown time zone: {}", name))
+}
+
+/// Parses an RFC 3339 timestamp, or a `YYYY-MM-DD` date taken as midnight in `tz`.
+pub fn parse_datetime(value: &str, tz: Tz) -> Result<DateTime<Utc>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow!("Invalid date: {}", value))?;
+    start_of_day(date, tz)
+}
+
+/// Returns the `[start, end)` range covering the current day in `tz`.
+pub fn today_bounds(now: DateTime<Utc>, tz: Tz) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let today = now.with_timezone(&tz).date_naive();
+    Ok((
+        start_of_day(today, tz)?,
+        start_of_day(today + Duration::days(1), tz)?,
+    ))
+}
+
+/// Returns the `[start, end)` range covering the current Monday-based week in `tz`.
+pub fn this_week_bounds(now: DateTime<Utc>, tz: Tz) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let today = now.with_timezone(&tz).date_naive();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    Ok((
+        start_of_day(monday, tz)?,
+        start_of_day(monday + Duration::days(7), tz)?,
+    ))
+}
+
+/// Midnight at the start of `date` in `tz`, as UTC.
+pub fn start_of_day(date: NaiveDate, tz: Tz) -> Result<DateTime<Utc>> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    // Midnight can be skipped or repeated around DST changes; take the earliest instant
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(midnight + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("Invalid local time for {}", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn today_follows_the_local_date() {
+        // 22:00 on the 15th in New York is already the 16th in UTC
+        let bounds = today_bounds(utc("2026-01-16T03:00:00Z"), chrono_tz::America::New_York);
+        assert_eq!(
+            bounds.unwrap(),
+            (utc("2026-01-15T05:00:00Z"), utc("2026-01-16T05:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn days_shrink_and_grow_across_dst_changes() {
+        let berlin = chrono_tz::Europe::Berlin;
+
+        let (start, end) = today_bounds(utc("2026-03-29T10:00:00Z"), berlin).unwrap();
+        assert_eq!(
+            (start, end),
+            (utc("2026-03-28T23:00:00Z"), utc("2026-03-29T22:00:00Z"))
+        );
+        assert_eq!(end - start, Duration::hours(23));
+
+        // `tomorrow` seen from the Saturday before clocks go back
+        let saturday = utc("2026-10-24T12:00:00Z")
+            .with_timezone(&berlin)
+            .date_naive();
+        let tomorrow = start_of_day(saturday + Duration::days(1), berlin).unwrap();
+        let day_after = start_of_day(saturday + Duration::days(2), berlin).unwrap();
+        assert_eq!(tomorrow, utc("2026-10-24T22:00:00Z"));
+        assert_eq!(day_after - tomorrow, Duration::hours(25));
+    }
+
+    #[test]
+    fn weeks_turn_over_at_local_midnight_on_monday() {
+        let new_york = chrono_tz::America::New_York;
+
+        // Sunday evening, the day clocks went forward: still the old week
+        let bounds = this_week_bounds(utc("2026-03-09T02:30:00Z"), new_york);
+        assert_eq!(
+            bounds.unwrap(),
+            (utc("2026-03-02T05:00:00Z"), utc("2026-03-09T04:00:00Z"))
+        );
+
+        // Half an hour into Monday
+        let bounds = this_week_bounds(utc("2026-03-09T04:30:00Z"), new_york);
+        assert_eq!(
+            bounds.unwrap(),
+            (utc("2026-03-09T04:00:00Z"), utc("2026-03-16T04:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn skipped_midnight_starts_the_day_an_hour_later() {
+        let date = NaiveDate::from_ymd_opt(2018, 11, 4).unwrap();
+        let start = start_of_day(date, chrono_tz::America::Sao_Paulo).unwrap();
+        assert_eq!(start, utc("2018-11-04T03:00:00Z"));
+    }
+}
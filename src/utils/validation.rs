@@ -0,0 +1,70 @@
+use std::borrow::Cow;
+
+use validator::ValidationError;
+
+use crate::models::tag::is_valid_color;
+use crate::utils::time::parse_timezone;
+
+fn invalid(code: &'static str, message: &'static str) -> ValidationError {
+    ValidationError::new(code).with_message(Cow::Borrowed(message))
+}
+
+/// Rejects strings that are empty once surrounding whitespace is removed.
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(invalid("blank", "must not be empty"));
+    }
+    Ok(())
+}
+
+/// Passwords need at least one letter and one digit; the length is checked
+/// separately.
+pub fn password_policy(value: &str) -> Result<(), ValidationError> {
+    let has_letter = value.chars().any(char::is_alphabetic);
+    let has_digit = value.chars().any(|c| c.is_ascii_digit());
+    if !has_letter || !has_digit {
+        return Err(invalid(
+            "weak_password",
+            "must contain at least one letter and one digit",
+        ));
+    }
+    Ok(())
+}
+
+pub fn valid_timezone(value: &str) -> Result<(), ValidationError> {
+    parse_timezone(value)
+        .map(|_| ())
+        .map_err(|_| invalid("timezone", "must be an IANA time zone, e.g. Europe/Berlin"))
+}
+
+pub fn valid_color(value: &str) -> Result<(), ValidationError> {
+    if !is_valid_color(value) {
+        return Err(invalid("color", "must be in #RRGGBB format"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_strings_are_rejected() {
+        assert!(not_blank("  \t\n").is_err());
+        assert!(not_blank(" milk ").is_ok());
+    }
+
+    #[test]
+    fn passwords_need_letters_and_digits() {
+        assert!(password_policy("password").is_err());
+        assert!(password_policy("12345678").is_err());
+        assert!(password_policy("passw0rd").is_ok());
+    }
+
+    #[test]
+    fn colors_must_be_hex_triplets() {
+        assert!(valid_color("#1a2B3c").is_ok());
+        assert!(valid_color("1a2b3c").is_err());
+        assert!(valid_color("#12345g").is_err());
+    }
+}